@@ -0,0 +1,255 @@
+//! Compatibility layer for Zebra Browser Print, so web apps written against
+//! `BrowserPrint.js` work with Dazzle in place of Zebra's agent.
+//!
+//! Browser Print devices are plain printers here: the device `name` and `uid`
+//! are both the printer name, and everything sent to `/write` goes through
+//! the normal job pipeline.
+
+use crate::jobs::{self, JobStatus};
+use crate::metrics;
+use crate::printing::{self, Printer};
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Version reported to clients. Browser Print JS only checks that it parses.
+const VERSION: &str = "1.3.2.489";
+
+/// A device in Browser Print's wire format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Device {
+    #[serde(default)]
+    pub device_type: String,
+    #[serde(default)]
+    pub uid: String,
+    #[serde(default)]
+    pub provider: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub connection: String,
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub manufacturer: String,
+}
+
+impl From<&Printer> for Device {
+    fn from(printer: &Printer) -> Self {
+        Self {
+            device_type: "printer".to_string(),
+            uid: printer.name.clone(),
+            provider: "com.zebra.ds.webdriver.desktop.provider.DefaultDeviceProvider".to_string(),
+            name: printer.name.clone(),
+            connection: "driver".to_string(),
+            version: 3,
+            manufacturer: "Zebra Technologies".to_string(),
+        }
+    }
+}
+
+impl Device {
+    /// The printer this device refers to. Clients echo back what we sent, but
+    /// fall back to `uid` for ones that only keep that around.
+    fn printer_name(&self) -> Option<&str> {
+        [self.name.as_str(), self.uid.as_str()]
+            .into_iter()
+            .find(|s| !s.is_empty())
+    }
+}
+
+#[derive(Deserialize)]
+struct DefaultQuery {
+    #[serde(rename = "type")]
+    device_type: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AvailableResponse {
+    printer: Vec<Device>,
+}
+
+#[derive(Deserialize)]
+struct WriteRequest {
+    device: Device,
+    #[serde(default)]
+    data: String,
+}
+
+#[derive(Deserialize)]
+struct ReadRequest {
+    #[allow(dead_code)]
+    device: Device,
+}
+
+/// The Browser Print routes, merged into the main router when enabled.
+pub fn routes() -> Router<Arc<crate::AppState>> {
+    Router::new()
+        .route("/available", get(available_handler))
+        .route("/default", get(default_handler))
+        .route("/write", post(write_handler))
+        .route("/read", post(read_handler))
+        .route("/config", get(config_handler))
+}
+
+async fn discover(state: &Arc<crate::AppState>) -> Result<Vec<Printer>, (StatusCode, String)> {
+    let state = state.clone();
+    tokio::task::spawn_blocking(move || metrics::discover(&state))
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Task panicked: {e}"),
+            )
+        })?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+async fn available_handler(
+    State(state): State<Arc<crate::AppState>>,
+) -> Result<Json<AvailableResponse>, (StatusCode, String)> {
+    let printers = discover(&state).await?;
+    Ok(Json(AvailableResponse {
+        printer: printers.iter().map(Device::from).collect(),
+    }))
+}
+
+/// `GET /default?type=printer`. Browser Print answers with an empty body when
+/// there's no default, which its JS treats as "no device".
+async fn default_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Query(query): Query<DefaultQuery>,
+) -> Result<Json<Option<Device>>, (StatusCode, String)> {
+    if query.device_type.as_deref().is_some_and(|t| t != "printer") {
+        return Ok(Json(None));
+    }
+
+    let selected = state
+        .config
+        .read()
+        .ok()
+        .and_then(|c| c.selected_printer.clone());
+    let printers = discover(&state).await?;
+
+    let default = match selected {
+        Some(name) => printers
+            .iter()
+            .find(|p| p.name == name)
+            .map(Device::from)
+            // The selected printer may not be discoverable (e.g. a raw queue
+            // on another host) but it's still where jobs go
+            .or_else(|| {
+                Some(Device::from(&Printer {
+                    name,
+                    is_default: true,
+                    alias_for: None,
+                    paused: false,
+                }))
+            }),
+        None => printers.iter().find(|p| p.is_default).map(Device::from),
+    };
+
+    Ok(Json(default))
+}
+
+/// `POST /write`. The body is JSON, but sent as `text/plain` to avoid a CORS
+/// preflight, so it's parsed by hand.
+async fn write_handler(
+    State(state): State<Arc<crate::AppState>>,
+    body: Bytes,
+) -> Result<(), (StatusCode, String)> {
+    let request: WriteRequest = serde_json::from_slice(&body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {e}")))?;
+
+    if request.data.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Empty data".to_string()));
+    }
+
+    let printer = request
+        .device
+        .printer_name()
+        .ok_or((StatusCode::BAD_REQUEST, "No device given".to_string()))?
+        .to_string();
+
+    let job = jobs::run(&state, printer, request.data.into_bytes())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    match job.status {
+        JobStatus::Failed => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            job.error.unwrap_or_default(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// `POST /read`. Reading back from the printer isn't possible through the
+/// spooler, so there's never anything to return.
+async fn read_handler(body: Bytes) -> Result<String, (StatusCode, String)> {
+    serde_json::from_slice::<ReadRequest>(&body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {e}")))?;
+    Ok(String::new())
+}
+
+async fn config_handler() -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "application": {
+            "supportedConversions": {},
+            "version": VERSION,
+            "build_number": 489,
+            "api_level": 5,
+            "platform": std::env::consts::OS,
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn printer_maps_to_device() {
+        let device = Device::from(&Printer {
+            name: "ZebraRaw".to_string(),
+            is_default: true,
+            alias_for: None,
+            paused: false,
+        });
+
+        let json = serde_json::to_value(&device).unwrap();
+        assert_eq!(json["deviceType"], "printer");
+        assert_eq!(json["uid"], "ZebraRaw");
+        assert_eq!(json["name"], "ZebraRaw");
+        assert_eq!(json["connection"], "driver");
+    }
+
+    #[test]
+    fn write_request_parses_browser_print_payload() {
+        let body = r#"{"device":{"deviceType":"printer","uid":"ZebraRaw","provider":"com.zebra.ds.webdriver.desktop.provider.DefaultDeviceProvider","name":"ZebraRaw","connection":"driver","version":3,"manufacturer":"Zebra Technologies"},"data":"^XA^FDHello^FS^XZ"}"#;
+
+        let request: WriteRequest = serde_json::from_str(body).unwrap();
+
+        assert_eq!(request.device.printer_name(), Some("ZebraRaw"));
+        assert_eq!(request.data, "^XA^FDHello^FS^XZ");
+    }
+
+    #[test]
+    fn device_name_falls_back_to_uid() {
+        let request: WriteRequest =
+            serde_json::from_str(r#"{"device":{"uid":"ZebraRaw"},"data":"^XA^XZ"}"#).unwrap();
+        assert_eq!(request.device.printer_name(), Some("ZebraRaw"));
+
+        let request: WriteRequest =
+            serde_json::from_str(r#"{"device":{},"data":"^XA^XZ"}"#).unwrap();
+        assert_eq!(request.device.printer_name(), None);
+    }
+}
@@ -1,83 +1,249 @@
-use crate::{autostart, config, printing, server, AppState};
+use crate::error::{Error, ErrorCode};
+use crate::{
+    autostart, config, holds, jobs, limits, metrics, printing, schedule, tls, webhooks, AppState,
+};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 #[tauri::command]
-pub fn list_printers() -> Result<Vec<printing::Printer>, String> {
-    printing::discover()
+pub fn list_printers(
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<Vec<printing::Printer>, Error> {
+    let mut printers = metrics::discover(&state)?;
+    state.holds.mark(&mut printers);
+    Ok(printers)
 }
 
+/// Print `zpl` from the app through the same pipeline as every listener, so
+/// pauses, pools, aliases and the job history apply.
 #[tauri::command]
-pub fn print_zpl(printer: String, zpl: String) -> Result<(), String> {
-    printing::send_raw(&printer, zpl.as_bytes())?;
-    log::info!("Printed {} bytes to {printer}", zpl.len());
-    Ok(())
+pub async fn print_zpl(
+    printer: String,
+    zpl: String,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<jobs::PrintJob, Error> {
+    let job = jobs::run(&state, printer, zpl.into_bytes()).await?;
+    match job.status {
+        jobs::JobStatus::Failed => {
+            let code = job.error_code.unwrap_or(ErrorCode::SpoolerError);
+            Err(Error::new(code, job.error.unwrap_or_default()).with_job_id(job.id))
+        }
+        _ => Ok(job),
+    }
 }
 
 #[tauri::command]
-pub fn get_config(state: tauri::State<'_, Arc<AppState>>) -> Result<config::AppConfig, String> {
-    state
-        .config
-        .read()
-        .map(|c| c.clone())
-        .map_err(|e| e.to_string())
+pub fn get_config(state: tauri::State<'_, Arc<AppState>>) -> Result<config::AppConfig, Error> {
+    state.config.read().map(|c| c.clone()).map_err(Error::from)
 }
 
 #[tauri::command]
 pub async fn set_config(
     new_config: config::AppConfig,
     state: tauri::State<'_, Arc<AppState>>,
-) -> Result<(), String> {
-    let port_changed = {
-        let current = state.config.read().map_err(|e| e.to_string())?;
-        current.port != new_config.port
+) -> Result<(), Error> {
+    new_config
+        .validate()
+        .map_err(|e| Error::new(ErrorCode::InvalidRequest, e))?;
+    let restart_needed = {
+        let current = state.config.read()?;
+        current.needs_server_restart(&new_config)
     };
 
-    config::save(&new_config)?;
+    save(&new_config)?;
 
     {
-        let mut config = state.config.write().map_err(|e| e.to_string())?;
+        let mut config = state.config.write()?;
         *config = new_config;
     }
 
-    if port_changed {
-        crate::restart_server(state.inner()).await?;
+    if restart_needed {
+        restart(state.inner()).await?;
     }
 
     Ok(())
 }
 
 #[tauri::command]
-pub async fn get_server_running(state: tauri::State<'_, Arc<AppState>>) -> Result<bool, String> {
+pub async fn get_server_running(state: tauri::State<'_, Arc<AppState>>) -> Result<bool, Error> {
     let handle = state.server_handle.lock().await;
     Ok(handle.as_ref().is_some_and(|h| !h.is_finished()))
 }
 
 #[tauri::command]
-pub async fn restart_server(state: tauri::State<'_, Arc<AppState>>) -> Result<(), String> {
-    crate::restart_server(state.inner()).await
+pub async fn restart_server(state: tauri::State<'_, Arc<AppState>>) -> Result<(), Error> {
+    restart(state.inner()).await
+}
+
+async fn restart(state: &Arc<AppState>) -> Result<(), Error> {
+    crate::restart_server(state)
+        .await
+        .map_err(|e| Error::new(ErrorCode::ServerError, e))
+}
+
+fn save(config: &config::AppConfig) -> Result<(), Error> {
+    config::save(config).map_err(|e| Error::new(ErrorCode::ConfigError, e))
 }
 
 #[tauri::command]
-pub fn get_autostart() -> Result<bool, String> {
-    autostart::is_enabled()
+pub fn get_autostart() -> Result<bool, Error> {
+    autostart::is_enabled().map_err(Error::internal)
 }
 
 #[tauri::command]
-pub fn set_autostart(enabled: bool) -> Result<(), String> {
-    if enabled {
+pub fn set_autostart(enabled: bool) -> Result<(), Error> {
+    let result = if enabled {
         autostart::enable()
     } else {
         autostart::disable()
+    };
+    result.map_err(Error::internal)
+}
+
+#[tauri::command]
+pub fn get_printer_aliases(
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<BTreeMap<String, String>, Error> {
+    state
+        .config
+        .read()
+        .map(|c| c.aliases.clone())
+        .map_err(Error::from)
+}
+
+/// Add or repoint an alias. `target` is a printer name or `tcp://host:port`.
+#[tauri::command]
+pub fn set_printer_alias(
+    alias: String,
+    target: String,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), Error> {
+    let alias = alias.trim().to_string();
+    let target = target.trim().to_string();
+    if alias.is_empty() || target.is_empty() {
+        return Err(Error::new(
+            ErrorCode::InvalidRequest,
+            "Alias and target printer are required",
+        ));
+    }
+    if alias == target {
+        return Err(Error::new(
+            ErrorCode::InvalidRequest,
+            "An alias can't point at itself",
+        ));
     }
+
+    update_aliases(&state, |aliases| {
+        aliases.insert(alias, target);
+    })
 }
 
+#[tauri::command]
+pub fn remove_printer_alias(
+    alias: String,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), Error> {
+    update_aliases(&state, |aliases| {
+        aliases.remove(&alias);
+    })
+}
+
+/// Apply `change` to the aliases and persist the config. Aliases are read on
+/// every job, so the server doesn't need a restart.
+fn update_aliases(
+    state: &AppState,
+    change: impl FnOnce(&mut BTreeMap<String, String>),
+) -> Result<(), Error> {
+    let mut config = state.config.write()?;
+    let mut updated = config.clone();
+    change(&mut updated.aliases);
+    save(&updated)?;
+    *config = updated;
+    Ok(())
+}
+
+/// Copy the local CA certificate to `path`, or to the Downloads folder
+/// without one, so it can be installed as trusted. Returns where it went.
+#[tauri::command]
+pub fn export_tls_ca(path: Option<String>) -> Result<String, Error> {
+    let path = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => dirs::download_dir()
+            .or_else(dirs::home_dir)
+            .unwrap_or_else(|| std::path::PathBuf::from("."))
+            .join("dazzle-local-ca.pem"),
+    };
+    tls::export_ca(&path).map_err(Error::internal)?;
+    log::info!("Exported CA certificate to {}", path.display());
+    Ok(path.display().to_string())
+}
+
+/// The job history, newest first. With `search`, only jobs matching every
+/// term of it (see [`jobs::PrintJob::matches`]).
 #[tauri::command]
 pub fn get_print_jobs(
+    search: Option<String>,
     state: tauri::State<'_, Arc<AppState>>,
-) -> Result<Vec<server::PrintJob>, String> {
+) -> Result<Vec<jobs::PrintJob>, Error> {
+    let jobs = state.print_jobs.read()?;
+    Ok(match search.as_deref().map(str::trim) {
+        Some(search) if !search.is_empty() => jobs
+            .iter()
+            .filter(|job| job.matches(search))
+            .cloned()
+            .collect(),
+        _ => jobs.clone(),
+    })
+}
+
+#[tauri::command]
+pub fn get_webhook_deliveries(
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<Vec<webhooks::Delivery>, Error> {
     state
-        .print_jobs
+        .webhook_deliveries
         .read()
-        .map(|jobs| jobs.clone())
-        .map_err(|e| e.to_string())
+        .map(|deliveries| deliveries.clone())
+        .map_err(Error::from)
+}
+
+#[tauri::command]
+pub fn get_printing_paused(state: tauri::State<'_, Arc<AppState>>) -> bool {
+    state.limits.is_paused()
+}
+
+/// The panic button: refuse every new job until printing is resumed. Jobs
+/// already being sent finish.
+#[tauri::command]
+pub fn set_printing_paused(paused: bool, state: tauri::State<'_, Arc<AppState>>) {
+    limits::set_paused(&state, paused);
+}
+
+/// Hold new jobs for `printer` (or resume it), such as while its labels are
+/// changed.
+#[tauri::command]
+pub async fn set_printer_paused(
+    printer: String,
+    paused: bool,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<holds::PrinterPaused, Error> {
+    let state = state.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        let printer = holds::resolve(&state, &printer)?;
+        Ok(holds::set_paused(&state, &printer, paused))
+    })
+    .await?
+}
+
+#[tauri::command]
+pub fn get_scheduled_jobs(state: tauri::State<'_, Arc<AppState>>) -> Vec<jobs::PrintJob> {
+    state.schedule.jobs()
+}
+
+#[tauri::command]
+pub fn cancel_scheduled_job(
+    id: String,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<jobs::PrintJob, Error> {
+    schedule::cancel(&state, &id)
 }
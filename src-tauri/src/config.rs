@@ -1,10 +1,317 @@
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub port: u16,
     pub selected_printer: Option<String>,
+    /// Serve the API over HTTPS as well, using a locally generated certificate.
+    #[serde(default)]
+    pub tls_enabled: bool,
+    #[serde(default = "default_tls_port")]
+    pub tls_port: u16,
+    /// Address the listeners bind to. Loopback (the default) means both
+    /// `127.0.0.1` and `::1`; anything else is bound alongside loopback.
+    #[serde(default = "default_bind_address")]
+    pub bind_address: IpAddr,
+    #[serde(default)]
+    pub lan: LanConfig,
+    #[serde(default)]
+    pub raw: RawConfig,
+    #[serde(default)]
+    pub lpd: LpdConfig,
+    #[serde(default)]
+    pub browser_print: BrowserPrintConfig,
+    #[serde(default)]
+    pub qz: QzConfig,
+    /// Logical printer names (e.g. `shipping`) to the printer they print on,
+    /// which may be a local printer or a `tcp://host:port` address.
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    /// Named groups of printers that share jobs between them.
+    #[serde(default)]
+    pub pools: BTreeMap<String, PoolConfig>,
+    /// Routing rules for `POST /print`, checked in order. The first rule that
+    /// matches a job decides its printer.
+    #[serde(default)]
+    pub rules: Vec<RoutingRule>,
+    /// Named sets of printers that `POST /print` sends every job to at once.
+    #[serde(default)]
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// Where finished jobs are POSTed, and how deliveries are signed.
+    #[serde(default)]
+    pub webhooks: WebhookConfig,
+    /// Outbound connection to a relay that jobs are pulled from.
+    #[serde(default)]
+    pub relay: RelayConfig,
+    /// Tokens for the other Dazzle stations `dazzle://` printers forward to,
+    /// keyed by `host:port`.
+    #[serde(default)]
+    pub peer_tokens: BTreeMap<String, String>,
+    /// How long a `POST /print` `Idempotency-Key` is remembered, in seconds.
+    #[serde(default = "default_idempotency_window")]
+    pub idempotency_window_secs: u64,
+    /// Refusing identical jobs sent again within a few seconds.
+    #[serde(default)]
+    pub dedupe: DedupeConfig,
+    /// Request rate limits and the cap on jobs in progress.
+    #[serde(default)]
+    pub limits: LimitsConfig,
+}
+
+/// Guard against the same label being printed twice by a double click.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DedupeConfig {
+    pub enabled: bool,
+    /// How long after a job an identical one is refused, in seconds.
+    pub window_secs: u64,
+}
+
+impl Default for DedupeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_secs: 10,
+        }
+    }
+}
+
+/// Limits that stop a runaway client from flooding the printers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// HTTP requests a minute from one origin (or client address without
+    /// one). 0 for no limit.
+    pub per_origin_per_minute: u32,
+    /// HTTP requests a minute with one bearer token. 0 for no limit.
+    pub per_token_per_minute: u32,
+    /// Jobs printing or waiting to print at once, from every listener.
+    /// 0 for no limit.
+    pub max_jobs: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            per_origin_per_minute: 0,
+            per_token_per_minute: 0,
+            max_jobs: 100,
+        }
+    }
+}
+
+/// Endpoints told about every job once it has printed or failed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// URLs that receive every finished job.
+    pub urls: Vec<String>,
+    /// Key for the `X-Dazzle-Signature` HMAC. Deliveries are unsigned without one.
+    pub secret: Option<String>,
+    /// Let `POST /print` requests name their own `callback` URL.
+    pub allow_callbacks: bool,
+}
+
+/// Agent mode: pull jobs from a relay server over an outbound connection, for
+/// stations a cloud app can't reach directly.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelayConfig {
+    pub enabled: bool,
+    /// Base URL of the relay, e.g. `https://relay.example.com/stations`.
+    pub url: String,
+    /// Sent as a bearer token so the relay knows which station is asking.
+    pub station_key: String,
+    /// Printer for jobs that don't name one. Falls back to the selected printer.
+    pub printer: Option<String>,
+}
+
+/// A printer pool. Jobs sent to the pool's name go to one healthy member,
+/// moving on to the next member if printing fails.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolConfig {
+    /// Printer names or aliases, in failover order.
+    pub members: Vec<String>,
+    #[serde(default)]
+    pub strategy: PoolStrategy,
+}
+
+/// A routing rule. Every condition that is set must match; a rule with no
+/// conditions matches every job.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoutingRule {
+    /// Shown in the print history for jobs this rule routed.
+    pub name: String,
+    /// Printer, alias or pool that matching jobs go to.
+    pub printer: String,
+    /// Origin host (`returns.example.com`, `*.example.com`) or full origin
+    /// (`https://returns.example.com`) the request must come from.
+    pub origin: Option<String>,
+    /// Request headers that must have these values. `*` only requires the
+    /// header to be present.
+    pub headers: BTreeMap<String, String>,
+    /// Query parameters that must have these values. `*` only requires the
+    /// parameter to be present.
+    pub query: BTreeMap<String, String>,
+    /// Smallest payload, in bytes, the rule applies to.
+    pub min_size: Option<usize>,
+    /// Largest payload, in bytes, the rule applies to.
+    pub max_size: Option<usize>,
+    /// Regular expression the payload must match, e.g. `\^PW812`.
+    pub pattern: Option<crate::routing::Pattern>,
+}
+
+/// How a pool picks the member that gets the next job.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolStrategy {
+    /// Always the first healthy member, in configured order.
+    #[default]
+    Failover,
+    /// Each job starts at the next member.
+    RoundRobin,
+    /// The member with the fewest jobs currently printing.
+    LeastQueued,
+}
+
+/// "Share on LAN" mode: lets other machines print through this station.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanConfig {
+    pub enabled: bool,
+    /// Bearer token remote clients must send. LAN requests are refused until one is set.
+    pub token: Option<String>,
+    /// Client address ranges allowed to connect from the network.
+    pub allowed_networks: Vec<IpNet>,
+    /// Advertise the server as `_dazzle._tcp` over mDNS.
+    pub advertise_mdns: bool,
+}
+
+impl Default for LanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            token: None,
+            allowed_networks: [
+                "10.0.0.0/8",
+                "172.16.0.0/12",
+                "192.168.0.0/16",
+                "fc00::/7",
+                "fe80::/10",
+            ]
+            .iter()
+            .filter_map(|n| n.parse().ok())
+            .collect(),
+            advertise_mdns: true,
+        }
+    }
+}
+
+/// Raw TCP listener that makes Dazzle look like a network printer to software
+/// that can only print to an IP address. Each connection is one job.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RawConfig {
+    pub enabled: bool,
+    pub port: u16,
+    /// Printer that receives raw jobs. Falls back to the selected printer.
+    pub printer: Option<String>,
+}
+
+impl Default for RawConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 9100,
+            printer: None,
+        }
+    }
+}
+
+/// RFC 1179 LPD server for clients that only print over LPR.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LpdConfig {
+    pub enabled: bool,
+    /// Defaults to 5515 rather than 515 so it can run without privileges.
+    pub port: u16,
+    /// LPD queue name to local printer name.
+    pub queues: BTreeMap<String, String>,
+}
+
+impl Default for LpdConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 5515,
+            queues: BTreeMap::new(),
+        }
+    }
+}
+
+/// Zebra Browser Print compatible endpoints, for web apps that only know how
+/// to talk to Zebra's agent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BrowserPrintConfig {
+    pub enabled: bool,
+    /// Browser Print's well-known HTTP port, which its JS library hardcodes.
+    pub port: u16,
+    /// Browser Print's HTTPS port, served when HTTPS is enabled.
+    pub tls_port: u16,
+}
+
+impl Default for BrowserPrintConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 9100,
+            tls_port: 9101,
+        }
+    }
+}
+
+/// QZ Tray compatible WebSocket API, so `qz-tray.js` integrations can print
+/// through Dazzle without changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QzConfig {
+    pub enabled: bool,
+    /// QZ Tray's insecure WebSocket port.
+    pub port: u16,
+    /// QZ Tray's secure WebSocket port, served when HTTPS is enabled.
+    pub tls_port: u16,
+    /// SHA-256 fingerprints of the site certificates allowed to print. Empty
+    /// allows any site, signed or not.
+    pub trusted_certificates: Vec<String>,
+}
+
+impl Default for QzConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 8182,
+            tls_port: 8181,
+            trusted_certificates: Vec::new(),
+        }
+    }
+}
+
+fn default_tls_port() -> u16 {
+    29443
+}
+
+fn default_bind_address() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::LOCALHOST)
+}
+
+fn default_idempotency_window() -> u64 {
+    24 * 60 * 60
 }
 
 impl Default for AppConfig {
@@ -12,25 +319,192 @@ impl Default for AppConfig {
         Self {
             port: 29100,
             selected_printer: None,
+            tls_enabled: false,
+            tls_port: default_tls_port(),
+            bind_address: default_bind_address(),
+            lan: LanConfig::default(),
+            raw: RawConfig::default(),
+            lpd: LpdConfig::default(),
+            browser_print: BrowserPrintConfig::default(),
+            qz: QzConfig::default(),
+            aliases: BTreeMap::new(),
+            pools: BTreeMap::new(),
+            rules: Vec::new(),
+            groups: BTreeMap::new(),
+            webhooks: WebhookConfig::default(),
+            relay: RelayConfig::default(),
+            peer_tokens: BTreeMap::new(),
+            idempotency_window_secs: default_idempotency_window(),
+            dedupe: DedupeConfig::default(),
+            limits: LimitsConfig::default(),
         }
     }
 }
 
-fn config_path() -> PathBuf {
+impl AppConfig {
+    /// Whether switching from `self` to `other` changes anything the running
+    /// listeners or relay agent were started with.
+    pub fn needs_server_restart(&self, other: &AppConfig) -> bool {
+        self.port != other.port
+            || self.tls_enabled != other.tls_enabled
+            || self.tls_port != other.tls_port
+            || self.bind_address != other.bind_address
+            || self.lan != other.lan
+            || self.raw != other.raw
+            || self.lpd.enabled != other.lpd.enabled
+            || self.lpd.port != other.lpd.port
+            || self.browser_print != other.browser_print
+            || self.qz.enabled != other.qz.enabled
+            || self.qz.port != other.qz.port
+            || self.qz.tls_port != other.qz.tls_port
+            || self.relay != other.relay
+    }
+
+    /// Check the config can be applied: every enabled listener has a port of
+    /// its own and every routing pattern compiles.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut ports = vec![("the HTTP server", self.port)];
+        if self.tls_enabled {
+            ports.push(("HTTPS", self.tls_port));
+        }
+        if self.raw.enabled {
+            ports.push(("raw TCP printing", self.raw.port));
+        }
+        if self.lpd.enabled {
+            ports.push(("LPD", self.lpd.port));
+        }
+        if self.browser_print.enabled {
+            ports.push(("Browser Print", self.browser_print.port));
+            if self.tls_enabled {
+                ports.push(("Browser Print over HTTPS", self.browser_print.tls_port));
+            }
+        }
+        if self.qz.enabled {
+            ports.push(("QZ Tray", self.qz.port));
+            if self.tls_enabled {
+                ports.push(("QZ Tray over HTTPS", self.qz.tls_port));
+            }
+        }
+        for (i, (name, port)) in ports.iter().enumerate() {
+            if let Some((other, _)) = ports[..i].iter().find(|(_, p)| p == port) {
+                return Err(format!("{other} and {name} both use port {port}"));
+            }
+        }
+
+        for rule in &self.rules {
+            if let Some(e) = rule.pattern.as_ref().and_then(|p| p.error()) {
+                return Err(format!(
+                    "Routing rule {:?} has an invalid pattern: {e}",
+                    rule.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The printer a job for `printer` should go to: the alias target if
+    /// `printer` is an alias, otherwise `printer` itself.
+    pub fn resolve_printer(&self, printer: &str) -> String {
+        self.aliases
+            .get(printer)
+            .cloned()
+            .unwrap_or_else(|| printer.to_string())
+    }
+
+    /// Whether a job may name `printer`. A `tcp://` or `dazzle://` address
+    /// must be set up in the config, as an alias or pool member or as a
+    /// printer picked in the settings, so a web page can't make the station
+    /// connect to any address it likes.
+    pub fn accepts_printer(&self, printer: &str) -> bool {
+        let network = printer.starts_with("tcp://") || crate::peers::is_peer(printer);
+        !network || self.is_configured_printer(printer)
+    }
+
+    /// Whether `printer` is named anywhere in the settings: selected, as an
+    /// alias or queue target, or in a rule, pool or group.
+    pub fn is_configured_printer(&self, printer: &str) -> bool {
+        let named = Some(printer);
+        self.selected_printer.as_deref() == named
+            || self.raw.printer.as_deref() == named
+            || self.relay.printer.as_deref() == named
+            || self.aliases.values().any(|p| p == printer)
+            || self.lpd.queues.values().any(|p| p == printer)
+            || self.rules.iter().any(|rule| rule.printer == printer)
+            || self
+                .pools
+                .values()
+                .any(|pool| pool.members.iter().any(|p| p == printer))
+            || self.groups.values().flatten().any(|p| p == printer)
+    }
+
+    /// Replace printer group names in `printers` with the group's members,
+    /// dropping repeats.
+    pub fn expand_groups(&self, printers: &[String]) -> Vec<String> {
+        let mut expanded: Vec<String> = Vec::new();
+        for printer in printers {
+            let members = match self.groups.get(printer) {
+                Some(members) => members.as_slice(),
+                None => std::slice::from_ref(printer),
+            };
+            for member in members {
+                if !expanded.contains(member) {
+                    expanded.push(member.clone());
+                }
+            }
+        }
+        expanded
+    }
+
+    /// Addresses the listeners should bind to. Loopback is always included so
+    /// the local browser keeps working; sharing on LAN with a loopback bind
+    /// address listens on every IPv4 interface.
+    pub fn listen_addrs(&self) -> Vec<IpAddr> {
+        let loopback = [
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ];
+
+        let extra = if !self.bind_address.is_loopback() {
+            self.bind_address
+        } else if self.lan.enabled {
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        } else {
+            return loopback.to_vec();
+        };
+
+        match extra {
+            // `::` is dual-stack on most platforms and already covers loopback
+            IpAddr::V6(ip) if ip.is_unspecified() => vec![extra],
+            IpAddr::V4(ip) if ip.is_unspecified() => vec![extra, loopback[1]],
+            _ => [extra].into_iter().chain(loopback).collect(),
+        }
+    }
+}
+
+/// The `dazzle` directory inside the platform config dir, created on demand.
+pub fn config_dir() -> PathBuf {
     let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
     path.push("dazzle");
     if let Err(e) = std::fs::create_dir_all(&path) {
         log::warn!("Failed to create config directory: {e}");
     }
-    path.push("config.json");
     path
 }
 
+fn config_path() -> PathBuf {
+    config_dir().join("config.json")
+}
+
 pub fn load() -> AppConfig {
     let path = config_path();
     match std::fs::read_to_string(&path) {
-        Ok(s) => match serde_json::from_str(&s) {
-            Ok(config) => config,
+        Ok(s) => match serde_json::from_str::<AppConfig>(&s) {
+            Ok(config) => {
+                if let Err(e) = config.validate() {
+                    log::warn!("Config at {} has a problem: {e}", path.display());
+                }
+                config
+            }
             Err(e) => {
                 log::warn!("Failed to parse config at {}: {e}", path.display());
                 AppConfig::default()
@@ -59,6 +533,8 @@ mod tests {
         let config = AppConfig::default();
         assert_eq!(config.port, 29100);
         assert!(config.selected_printer.is_none());
+        assert!(!config.tls_enabled);
+        assert_eq!(config.tls_port, 29443);
     }
 
     #[test]
@@ -66,6 +542,7 @@ mod tests {
         let config = AppConfig {
             port: 8080,
             selected_printer: Some("Zebra ZD420".to_string()),
+            ..AppConfig::default()
         };
 
         let json = serde_json::to_string(&config).unwrap();
@@ -95,6 +572,7 @@ mod tests {
         let config = AppConfig {
             port: 3000,
             selected_printer: Some("Test Printer".to_string()),
+            ..AppConfig::default()
         };
 
         let json = serde_json::to_string_pretty(&config).unwrap();
@@ -128,6 +606,7 @@ mod tests {
         let config = AppConfig {
             port: 29100,
             selected_printer: None,
+            ..AppConfig::default()
         };
 
         let json: serde_json::Value = serde_json::to_value(&config).unwrap();
@@ -141,6 +620,7 @@ mod tests {
         let config = AppConfig {
             port: 1,
             selected_printer: None,
+            ..AppConfig::default()
         };
         let json = serde_json::to_string(&config).unwrap();
         let loaded: AppConfig = serde_json::from_str(&json).unwrap();
@@ -150,6 +630,7 @@ mod tests {
         let config = AppConfig {
             port: 65535,
             selected_printer: None,
+            ..AppConfig::default()
         };
         let json = serde_json::to_string(&config).unwrap();
         let loaded: AppConfig = serde_json::from_str(&json).unwrap();
@@ -170,6 +651,7 @@ mod tests {
         let config = AppConfig {
             port: 29100,
             selected_printer: Some("Druckerei-Schreibmaschine".to_string()),
+            ..AppConfig::default()
         };
 
         let json = serde_json::to_string(&config).unwrap();
@@ -179,4 +661,266 @@ mod tests {
             Some("Druckerei-Schreibmaschine")
         );
     }
+
+    #[test]
+    fn config_without_tls_fields_uses_defaults() {
+        let json = r#"{"port":29100,"selected_printer":null}"#;
+        let config: AppConfig = serde_json::from_str(json).unwrap();
+        assert!(!config.tls_enabled);
+        assert_eq!(config.tls_port, 29443);
+    }
+
+    #[test]
+    fn restart_needed_only_for_listener_changes() {
+        let current = AppConfig::default();
+
+        let printer_only = AppConfig {
+            selected_printer: Some("ZebraRaw".to_string()),
+            ..AppConfig::default()
+        };
+        assert!(!current.needs_server_restart(&printer_only));
+
+        let tls = AppConfig {
+            tls_enabled: true,
+            ..AppConfig::default()
+        };
+        assert!(current.needs_server_restart(&tls));
+    }
+
+    #[test]
+    fn default_binds_loopback_only() {
+        let config = AppConfig::default();
+        assert!(!config.lan.enabled);
+        assert!(config.lan.advertise_mdns);
+        assert_eq!(
+            config.listen_addrs(),
+            vec![
+                "127.0.0.1".parse::<IpAddr>().unwrap(),
+                "::1".parse::<IpAddr>().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn lan_mode_with_loopback_bind_listens_on_all_ipv4() {
+        let config = AppConfig {
+            lan: LanConfig {
+                enabled: true,
+                ..LanConfig::default()
+            },
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            config.listen_addrs(),
+            vec![
+                "0.0.0.0".parse::<IpAddr>().unwrap(),
+                "::1".parse::<IpAddr>().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn specific_bind_address_keeps_loopback() {
+        let config = AppConfig {
+            bind_address: "192.168.1.20".parse().unwrap(),
+            ..AppConfig::default()
+        };
+        assert_eq!(config.listen_addrs().len(), 3);
+        assert_eq!(config.listen_addrs()[0], config.bind_address);
+    }
+
+    #[test]
+    fn lan_config_roundtrips_networks_as_strings() {
+        let json = r#"{"port":29100,"selected_printer":null,"bind_address":"0.0.0.0","lan":{"enabled":true,"token":"secret","allowed_networks":["10.1.0.0/16"]}}"#;
+        let config: AppConfig = serde_json::from_str(json).unwrap();
+        assert!(config.lan.enabled);
+        assert_eq!(config.lan.token.as_deref(), Some("secret"));
+        assert_eq!(config.lan.allowed_networks.len(), 1);
+        // Missing fields inside `lan` fall back to their defaults
+        assert!(config.lan.advertise_mdns);
+
+        let out = serde_json::to_value(&config).unwrap();
+        assert_eq!(out["bind_address"], "0.0.0.0");
+        assert_eq!(out["lan"]["allowed_networks"][0], "10.1.0.0/16");
+    }
+
+    #[test]
+    fn lan_changes_require_restart() {
+        let current = AppConfig::default();
+        let shared = AppConfig {
+            lan: LanConfig {
+                enabled: true,
+                ..LanConfig::default()
+            },
+            ..AppConfig::default()
+        };
+        assert!(current.needs_server_restart(&shared));
+    }
+
+    #[test]
+    fn raw_listener_is_off_by_default() {
+        let json = r#"{"port":29100,"selected_printer":null}"#;
+        let config: AppConfig = serde_json::from_str(json).unwrap();
+        assert!(!config.raw.enabled);
+        assert_eq!(config.raw.port, 9100);
+        assert!(config.raw.printer.is_none());
+    }
+
+    #[test]
+    fn lpd_queue_changes_do_not_restart_server() {
+        let current = AppConfig::default();
+        let mut mapped = AppConfig::default();
+        mapped
+            .lpd
+            .queues
+            .insert("labels".to_string(), "ZebraRaw".to_string());
+        assert!(!current.needs_server_restart(&mapped));
+
+        let enabled = AppConfig {
+            lpd: LpdConfig {
+                enabled: true,
+                ..LpdConfig::default()
+            },
+            ..AppConfig::default()
+        };
+        assert!(current.needs_server_restart(&enabled));
+    }
+
+    #[test]
+    fn browser_print_uses_zebra_ports_and_restarts_server() {
+        let current = AppConfig::default();
+        assert!(!current.browser_print.enabled);
+        assert_eq!(current.browser_print.port, 9100);
+        assert_eq!(current.browser_print.tls_port, 9101);
+
+        let enabled = AppConfig {
+            browser_print: BrowserPrintConfig {
+                enabled: true,
+                ..BrowserPrintConfig::default()
+            },
+            ..AppConfig::default()
+        };
+        assert!(current.needs_server_restart(&enabled));
+    }
+
+    #[test]
+    fn listeners_cannot_share_a_port() {
+        let mut config = AppConfig::default();
+        config.raw.enabled = true;
+        assert!(config.validate().is_ok());
+
+        config.browser_print.enabled = true;
+        assert_eq!(
+            config.validate().unwrap_err(),
+            "raw TCP printing and Browser Print both use port 9100"
+        );
+
+        config.raw.port = 9200;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_routing_patterns_load_but_are_refused() {
+        let json = r#"{"port": 29100, "selected_printer": null, "rules": [{"name": "big", "printer": "Zebra", "pattern": "^PW("}]}"#;
+        let config: AppConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.rules[0].pattern.as_ref().unwrap().as_str(), "^PW(");
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .starts_with("Routing rule \"big\" has an invalid pattern"));
+    }
+
+    #[test]
+    fn qz_trusted_certificates_do_not_restart_server() {
+        let current = AppConfig::default();
+        let mut trusted = AppConfig::default();
+        trusted.qz.trusted_certificates.push("ab:cd".to_string());
+        assert!(!current.needs_server_restart(&trusted));
+
+        let mut moved = AppConfig::default();
+        moved.qz.port = 8283;
+        assert!(current.needs_server_restart(&moved));
+    }
+
+    #[test]
+    fn aliases_resolve_to_their_target() {
+        let mut config = AppConfig::default();
+        config
+            .aliases
+            .insert("shipping".to_string(), "ZebraRaw".to_string());
+        config
+            .aliases
+            .insert("product".to_string(), "tcp://10.0.0.5:9100".to_string());
+
+        assert_eq!(config.resolve_printer("shipping"), "ZebraRaw");
+        assert_eq!(config.resolve_printer("product"), "tcp://10.0.0.5:9100");
+        assert_eq!(config.resolve_printer("Office"), "Office");
+        assert!(!AppConfig::default().needs_server_restart(&config));
+    }
+
+    #[test]
+    fn only_configured_network_printers_are_accepted() {
+        let mut config = AppConfig::default();
+        config
+            .aliases
+            .insert("product".to_string(), "tcp://10.0.0.5:9100".to_string());
+        config.groups.insert(
+            "labels".to_string(),
+            vec!["Office".to_string(), "tcp://10.0.0.6:9100".to_string()],
+        );
+
+        assert!(config.accepts_printer("product"));
+        assert!(config.accepts_printer("Office"));
+        assert!(config.accepts_printer("tcp://10.0.0.5:9100"));
+        assert!(config.accepts_printer("tcp://10.0.0.6:9100"));
+        assert!(!config.accepts_printer("tcp://127.0.0.1:22"));
+        assert!(!config.accepts_printer("dazzle://127.0.0.1:8080/admin"));
+
+        config.pools.insert(
+            "desks".to_string(),
+            PoolConfig {
+                members: vec!["dazzle://192.168.1.20/Zebra".to_string()],
+                strategy: PoolStrategy::Failover,
+            },
+        );
+        assert!(config.accepts_printer("dazzle://192.168.1.20/Zebra"));
+    }
+
+    #[test]
+    fn pools_deserialize_with_default_strategy() {
+        let json = r#"{
+            "port": 29100,
+            "selected_printer": null,
+            "pools": {
+                "shipping": { "members": ["ZebraA", "ZebraB"] },
+                "labels": { "members": ["ZebraC"], "strategy": "least_queued" }
+            }
+        }"#;
+
+        let config: AppConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(config.pools["shipping"].members, ["ZebraA", "ZebraB"]);
+        assert_eq!(config.pools["shipping"].strategy, PoolStrategy::Failover);
+        assert_eq!(config.pools["labels"].strategy, PoolStrategy::LeastQueued);
+    }
+
+    #[test]
+    fn groups_expand_to_their_members_without_repeats() {
+        let mut config = AppConfig::default();
+        config.groups.insert(
+            "compliance".to_string(),
+            vec!["BoxZebra".to_string(), "ManifestZebra".to_string()],
+        );
+
+        let printers = |names: &[&str]| -> Vec<String> {
+            config.expand_groups(&names.iter().map(|n| n.to_string()).collect::<Vec<_>>())
+        };
+
+        assert_eq!(printers(&["compliance"]), ["BoxZebra", "ManifestZebra"]);
+        assert_eq!(
+            printers(&["Office", "compliance", "BoxZebra"]),
+            ["Office", "BoxZebra", "ManifestZebra"]
+        );
+        assert_eq!(printers(&["Office"]), ["Office"]);
+    }
 }
@@ -0,0 +1,112 @@
+//! Duplicate guard for `POST /print`: the same payload sent to the same
+//! printers again within a few seconds (a double click) is refused with the
+//! original job's id rather than printed twice.
+//!
+//! Only jobs that printed, or are still printing, count as originals. A job
+//! that failed is forgotten so it can be retried straight away.
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub type Fingerprint = [u8; 32];
+
+/// Hash of the printers a job goes to and its final payload.
+pub fn fingerprint(printers: &[String], data: &[u8]) -> Fingerprint {
+    let mut hasher = Sha256::new();
+    for printer in printers {
+        hasher.update(printer.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update([0]);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+/// Recently started jobs by fingerprint.
+#[derive(Default)]
+pub struct RecentJobs {
+    jobs: Mutex<HashMap<Fingerprint, (String, Instant)>>,
+}
+
+impl RecentJobs {
+    /// Record `job_id` as the job for `fingerprint`, unless another job had
+    /// it less than `window` ago, in which case that job's id is returned.
+    pub fn claim(
+        &self,
+        fingerprint: Fingerprint,
+        job_id: &str,
+        window: Duration,
+    ) -> Result<(), String> {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        jobs.retain(|_, (_, at)| now.duration_since(*at) < window);
+
+        if let Some((original, _)) = jobs.get(&fingerprint) {
+            return Err(original.clone());
+        }
+        jobs.insert(fingerprint, (job_id.to_string(), now));
+        Ok(())
+    }
+
+    /// Forget `job_id`, e.g. because it failed and may be sent again.
+    pub fn release(&self, fingerprint: &Fingerprint, job_id: &str) {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        if jobs.get(fingerprint).is_some_and(|(id, _)| id == job_id) {
+            jobs.remove(fingerprint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn fingerprint_covers_printers_and_payload() {
+        let a = fingerprint(&printers(&["ZebraA"]), b"^XA^XZ");
+        assert_eq!(a, fingerprint(&printers(&["ZebraA"]), b"^XA^XZ"));
+        assert_ne!(a, fingerprint(&printers(&["ZebraB"]), b"^XA^XZ"));
+        assert_ne!(a, fingerprint(&printers(&["ZebraA"]), b"^XA^FD1^XZ"));
+        assert_ne!(
+            fingerprint(&printers(&["ab", "c"]), b""),
+            fingerprint(&printers(&["a", "bc"]), b"")
+        );
+    }
+
+    #[test]
+    fn repeat_within_window_returns_original() {
+        let recent = RecentJobs::default();
+        let window = Duration::from_secs(10);
+        let label = fingerprint(&printers(&["ZebraA"]), b"^XA^XZ");
+
+        assert!(recent.claim(label, "job-1", window).is_ok());
+        assert_eq!(
+            recent.claim(label, "job-2", window),
+            Err("job-1".to_string())
+        );
+
+        let other = fingerprint(&printers(&["ZebraB"]), b"^XA^XZ");
+        assert!(recent.claim(other, "job-3", window).is_ok());
+    }
+
+    #[test]
+    fn expired_and_released_jobs_are_forgotten() {
+        let recent = RecentJobs::default();
+        let label = fingerprint(&printers(&["ZebraA"]), b"^XA^XZ");
+
+        recent.claim(label, "job-1", Duration::ZERO).unwrap();
+        assert!(recent.claim(label, "job-2", Duration::ZERO).is_ok());
+
+        let window = Duration::from_secs(10);
+        recent.claim(label, "job-3", window).unwrap_err();
+        recent.release(&label, "job-3");
+        recent.release(&label, "job-2");
+        assert!(recent.claim(label, "job-4", window).is_ok());
+    }
+}
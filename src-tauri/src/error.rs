@@ -0,0 +1,186 @@
+//! The error type shared by the HTTP API, the Tauri commands and printing.
+//!
+//! Every error carries a stable [`ErrorCode`] for clients to branch on, a
+//! human-readable message, optional details and, when a job was created, the
+//! job's id. Over HTTP it's sent as JSON with a status matching the code.
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::ToSchema;
+
+/// What went wrong, as a stable `snake_case` string on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The request had no data to print.
+    EmptyBody,
+    /// The body couldn't be decoded with the requested `encoding`.
+    InvalidEncoding,
+    /// A parameter or setting was malformed or not allowed.
+    InvalidRequest,
+    /// No printer was named and none is selected in the app.
+    NoPrinterSelected,
+    /// The printer doesn't exist on this machine.
+    PrinterNotFound,
+    /// No scheduled job has the given id.
+    JobNotFound,
+    /// The printer exists but can't be reached or isn't accepting jobs.
+    PrinterOffline,
+    /// The system spooler failed to take the job.
+    SpoolerError,
+    /// Another Dazzle station couldn't be reached or refused a forwarded job.
+    PeerError,
+    /// The same job was sent moments ago.
+    DuplicateJob,
+    /// Too many requests from this origin or token; see `Retry-After`.
+    RateLimited,
+    /// Too many jobs in progress; see `Retry-After`.
+    TooManyJobs,
+    /// Printing was paused from the app.
+    PrintingPaused,
+    Unauthorized,
+    Forbidden,
+    /// The settings couldn't be saved.
+    ConfigError,
+    /// The listeners couldn't be started.
+    ServerError,
+    Internal,
+}
+
+impl ErrorCode {
+    /// The HTTP status this code is sent with.
+    pub fn status(self) -> StatusCode {
+        match self {
+            Self::EmptyBody | Self::InvalidEncoding | Self::InvalidRequest => {
+                StatusCode::BAD_REQUEST
+            }
+            Self::NoPrinterSelected => StatusCode::BAD_REQUEST,
+            Self::PrinterNotFound | Self::JobNotFound => StatusCode::NOT_FOUND,
+            Self::PrinterOffline => StatusCode::SERVICE_UNAVAILABLE,
+            Self::PeerError => StatusCode::BAD_GATEWAY,
+            Self::DuplicateJob => StatusCode::CONFLICT,
+            Self::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            Self::TooManyJobs | Self::PrintingPaused => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::SpoolerError | Self::ConfigError | Self::ServerError | Self::Internal => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Extra context, such as the spooler's own output.
+    #[serde(default)]
+    pub details: Option<String>,
+    /// The job the error belongs to, if one was created.
+    #[serde(default)]
+    pub job_id: Option<String>,
+    /// Seconds to wait before trying again, sent as `Retry-After`.
+    #[serde(skip)]
+    pub retry_after: Option<u64>,
+}
+
+impl Error {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+            job_id: None,
+            retry_after: None,
+        }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+
+    pub fn with_details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+
+    pub fn with_job_id(mut self, job_id: impl Into<String>) -> Self {
+        self.job_id = Some(job_id.into());
+        self
+    }
+
+    pub fn with_retry_after(mut self, secs: u64) -> Self {
+        self.retry_after = Some(secs);
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.code.status()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.details {
+            Some(details) => write!(f, "{}: {details}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl<T> From<std::sync::PoisonError<T>> for Error {
+    fn from(e: std::sync::PoisonError<T>) -> Self {
+        Self::internal(e.to_string())
+    }
+}
+
+impl From<tokio::task::JoinError> for Error {
+    fn from(e: tokio::task::JoinError) -> Self {
+        Self::internal(format!("Task panicked: {e}"))
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let retry_after = self
+            .retry_after
+            .map(|secs| [(header::RETRY_AFTER, secs.to_string())]);
+        (self.status(), retry_after, Json(self)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_with_stable_code() {
+        let error = Error::new(ErrorCode::PrinterNotFound, "Printer Zebra does not exist")
+            .with_job_id("18c0ffee");
+
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "code": "printer_not_found",
+                "message": "Printer Zebra does not exist",
+                "details": null,
+                "job_id": "18c0ffee",
+            })
+        );
+        assert_eq!(error.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn display_includes_details() {
+        let error =
+            Error::new(ErrorCode::SpoolerError, "lp failed").with_details("lp: scheduler down");
+        assert_eq!(error.to_string(), "lp failed: lp: scheduler down");
+    }
+}
@@ -0,0 +1,315 @@
+//! Deep health check for `GET /health`. `/status` only says the server is
+//! up; this asks the spooler, looks for the selected printer and checks that
+//! the disk can be written and jobs aren't piling up.
+//!
+//! Every check passes, warns or fails. The overall status is the worst of
+//! them, and only a failure turns the response into a 503.
+
+use crate::error::Error;
+use crate::printing::{self, PrinterState};
+use crate::{config, metrics, peers, AppState};
+use axum::http::StatusCode;
+use serde::Serialize;
+use std::path::Path;
+use utoipa::ToSchema;
+
+/// Jobs being sent at once before the backlog is worth a warning.
+const BACKLOG_WARN: u64 = 10;
+/// Jobs being sent at once before the station is considered stuck.
+const BACKLOG_FAIL: u64 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Check {
+    /// `spooler`, `selected_printer`, `printer_status`, `disk` or `backlog`.
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Health {
+    /// The worst status of any check.
+    pub status: CheckStatus,
+    pub checks: Vec<Check>,
+}
+
+impl Health {
+    fn new(checks: Vec<Check>) -> Self {
+        let status = checks
+            .iter()
+            .map(|c| c.status)
+            .max()
+            .unwrap_or(CheckStatus::Pass);
+        Self { status, checks }
+    }
+
+    /// 503 if anything failed, so monitoring tools that only look at the
+    /// status code notice. Warnings still answer 200.
+    pub fn http_status(&self) -> StatusCode {
+        match self.status {
+            CheckStatus::Fail => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::OK,
+        }
+    }
+}
+
+/// Run every check. Blocks on the spooler, so call it off the async runtime.
+pub fn check(state: &AppState) -> Health {
+    let (selected, targets, is_pool) = match state.config.read() {
+        Ok(config) => {
+            let selected = config.selected_printer.clone();
+            let resolved = selected.as_deref().map(|s| config.resolve_printer(s));
+            match resolved {
+                Some(printer) => match config.pools.get(&printer) {
+                    Some(pool) => (
+                        selected,
+                        pool.members
+                            .iter()
+                            .map(|m| config.resolve_printer(m))
+                            .collect(),
+                        true,
+                    ),
+                    None => (selected, vec![printer], false),
+                },
+                None => (None, Vec::new(), false),
+            }
+        }
+        Err(_) => (None, Vec::new(), false),
+    };
+
+    let mut checks = vec![match printing::check_spooler() {
+        Ok(()) => Check::new("spooler", CheckStatus::Pass, "The spooler is running"),
+        Err(e) => Check::new("spooler", CheckStatus::Fail, e.to_string()),
+    }];
+
+    let discovered = if selected.is_some() {
+        metrics::discover(state)
+    } else {
+        Ok(Vec::new())
+    };
+    checks.push(selected_printer_check(
+        selected.as_deref(),
+        &targets,
+        is_pool,
+        &discovered,
+    ));
+
+    // Only printers the spooler knows about have a state to ask for
+    if let Ok(discovered) = &discovered {
+        let local: Vec<&String> = targets
+            .iter()
+            .filter(|t| discovered.iter().any(|p| &p.name == *t))
+            .collect();
+        let states: Vec<(&String, PrinterState)> = local
+            .into_iter()
+            .filter_map(|t| printing::printer_state(t).map(|s| (t, s)))
+            .collect();
+        if !states.is_empty() {
+            checks.push(printer_status_check(&states));
+        }
+    }
+
+    checks.push(disk_check(&[config::config_dir(), std::env::temp_dir()]));
+    checks.push(backlog_check(state.metrics.queue_depth()));
+
+    Health::new(checks)
+}
+
+/// Whether `target` can be found: network printers can't be discovered, so
+/// they're taken on trust.
+fn is_known(target: &str, discovered: &[printing::Printer]) -> bool {
+    target.starts_with("tcp://")
+        || peers::is_peer(target)
+        || discovered.iter().any(|p| p.name == target)
+}
+
+fn selected_printer_check(
+    selected: Option<&str>,
+    targets: &[String],
+    is_pool: bool,
+    discovered: &Result<Vec<printing::Printer>, Error>,
+) -> Check {
+    const NAME: &str = "selected_printer";
+
+    let Some(selected) = selected else {
+        return Check::new(NAME, CheckStatus::Warn, "No printer is selected");
+    };
+    let discovered = match discovered {
+        Ok(discovered) => discovered,
+        Err(e) => {
+            return Check::new(
+                NAME,
+                CheckStatus::Fail,
+                format!("Couldn't list printers: {e}"),
+            );
+        }
+    };
+
+    let missing: Vec<&str> = targets
+        .iter()
+        .filter(|t| !is_known(t, discovered))
+        .map(String::as_str)
+        .collect();
+
+    if missing.is_empty() {
+        Check::new(NAME, CheckStatus::Pass, format!("{selected} is available"))
+    } else if !is_pool {
+        Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("Printer {} not found", missing.join(", ")),
+        )
+    } else if missing.len() < targets.len() {
+        Check::new(
+            NAME,
+            CheckStatus::Warn,
+            format!("Pool {selected} members not found: {}", missing.join(", ")),
+        )
+    } else {
+        Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("None of the members of pool {selected} exist"),
+        )
+    }
+}
+
+fn printer_status_check(states: &[(&String, PrinterState)]) -> Check {
+    const NAME: &str = "printer_status";
+
+    let stopped: Vec<&str> = states
+        .iter()
+        .filter(|(_, state)| *state == PrinterState::Stopped)
+        .map(|(printer, _)| printer.as_str())
+        .collect();
+    if stopped.is_empty() {
+        Check::new(NAME, CheckStatus::Pass, "The printer is ready")
+    } else {
+        Check::new(
+            NAME,
+            CheckStatus::Warn,
+            format!("Stopped in the spooler: {}", stopped.join(", ")),
+        )
+    }
+}
+
+/// Whether a file can be created in each of `dirs`: the config directory,
+/// so settings can be saved, and the temp directory spoolers write through.
+fn disk_check(dirs: &[impl AsRef<Path>]) -> Check {
+    const NAME: &str = "disk";
+
+    for dir in dirs {
+        let dir = dir.as_ref();
+        let probe = dir.join(format!(".dazzle-health-{}", std::process::id()));
+        let written = std::fs::write(&probe, b"ok");
+        let _ = std::fs::remove_file(&probe);
+        if let Err(e) = written {
+            return Check::new(
+                NAME,
+                CheckStatus::Fail,
+                format!("Can't write to {}: {e}", dir.display()),
+            );
+        }
+    }
+    Check::new(NAME, CheckStatus::Pass, "The disk is writable")
+}
+
+fn backlog_check(depth: u64) -> Check {
+    const NAME: &str = "backlog";
+
+    let status = if depth >= BACKLOG_FAIL {
+        CheckStatus::Fail
+    } else if depth >= BACKLOG_WARN {
+        CheckStatus::Warn
+    } else {
+        CheckStatus::Pass
+    };
+    Check::new(NAME, status, format!("{depth} jobs being sent"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printers(names: &[&str]) -> Result<Vec<printing::Printer>, Error> {
+        Ok(names
+            .iter()
+            .map(|name| printing::Printer {
+                name: name.to_string(),
+                is_default: false,
+                alias_for: None,
+                paused: false,
+            })
+            .collect())
+    }
+
+    fn targets(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn selected_printer_must_exist() {
+        let discovered = printers(&["ZebraRaw"]);
+        let check = |selected, t: &[&str], is_pool| {
+            selected_printer_check(selected, &targets(t), is_pool, &discovered).status
+        };
+
+        assert_eq!(check(None, &[], false), CheckStatus::Warn);
+        assert_eq!(
+            check(Some("ZebraRaw"), &["ZebraRaw"], false),
+            CheckStatus::Pass
+        );
+        assert_eq!(check(Some("Gone"), &["Gone"], false), CheckStatus::Fail);
+        assert_eq!(
+            check(Some("net"), &["tcp://10.0.0.5:9100"], false),
+            CheckStatus::Pass
+        );
+        assert_eq!(
+            check(Some("line"), &["ZebraRaw", "Gone"], true),
+            CheckStatus::Warn
+        );
+        assert_eq!(check(Some("line"), &["Gone"], true), CheckStatus::Fail);
+    }
+
+    #[test]
+    fn overall_status_is_the_worst_check() {
+        let health = Health::new(vec![
+            backlog_check(0),
+            printer_status_check(&[(&"ZebraRaw".to_string(), PrinterState::Stopped)]),
+        ]);
+        assert_eq!(health.status, CheckStatus::Warn);
+        assert_eq!(health.http_status(), StatusCode::OK);
+
+        let health = Health::new(vec![backlog_check(3), backlog_check(BACKLOG_FAIL)]);
+        assert_eq!(health.status, CheckStatus::Fail);
+        assert_eq!(health.http_status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[test]
+    fn unwritable_directory_fails() {
+        let dir = std::env::temp_dir();
+        assert_eq!(disk_check(&[&dir]).status, CheckStatus::Pass);
+        assert_eq!(
+            disk_check(&[dir.join("does-not-exist").join("nested")]).status,
+            CheckStatus::Fail
+        );
+    }
+}
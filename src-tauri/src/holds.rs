@@ -0,0 +1,276 @@
+//! Per-printer pause, for changing label rolls. Jobs for a paused printer
+//! aren't refused or failed: they're held with [`JobStatus::Held`], listed
+//! in the history, and print in the order they arrived once the printer is
+//! resumed. Each held job takes a [`Ticket`] in its printer's queue and
+//! keeps it until it has been sent, so the next one waits its turn.
+//!
+//! Printers are paused by the name jobs resolve to, so pausing an alias
+//! pauses the printer behind it. A pool can be paused as a whole, and a
+//! paused member is skipped while the pool's other members can print.
+//!
+//! [`JobStatus::Held`]: crate::jobs::JobStatus::Held
+
+use crate::error::{Error, ErrorCode};
+use crate::printing::Printer;
+use crate::{metrics, peers, AppState};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tauri::Emitter;
+use tokio::sync::watch;
+use utoipa::ToSchema;
+
+pub struct Holds {
+    paused: watch::Sender<BTreeSet<String>>,
+    /// Tickets of the held jobs for each printer, oldest first.
+    queues: watch::Sender<BTreeMap<String, VecDeque<u64>>>,
+    next_ticket: AtomicU64,
+}
+
+impl Default for Holds {
+    fn default() -> Self {
+        Self {
+            paused: watch::Sender::new(BTreeSet::new()),
+            queues: watch::Sender::new(BTreeMap::new()),
+            next_ticket: AtomicU64::new(0),
+        }
+    }
+}
+
+/// A held job's place in its printer's queue, given up when dropped.
+pub struct Ticket<'a> {
+    holds: &'a Holds,
+    printer: String,
+    id: u64,
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        self.holds.queues.send_modify(|queues| {
+            if let Some(queue) = queues.get_mut(&self.printer) {
+                queue.retain(|id| *id != self.id);
+                if queue.is_empty() {
+                    queues.remove(&self.printer);
+                }
+            }
+        });
+    }
+}
+
+/// A printer's pause state, as answered by the pause and resume endpoints
+/// and sent to the UI in `printer-paused` events.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PrinterPaused {
+    /// The printer or pool, after resolving aliases.
+    pub printer: String,
+    pub paused: bool,
+}
+
+impl Holds {
+    pub fn is_paused(&self, printer: &str) -> bool {
+        self.paused.borrow().contains(printer)
+    }
+
+    /// Every paused printer and pool.
+    pub fn paused(&self) -> BTreeSet<String> {
+        self.paused.borrow().clone()
+    }
+
+    /// Pause or resume `printer`. Returns whether anything changed.
+    pub fn set_paused(&self, printer: &str, paused: bool) -> bool {
+        self.paused.send_if_modified(|set| {
+            if paused {
+                set.insert(printer.to_string())
+            } else {
+                set.remove(printer)
+            }
+        })
+    }
+
+    /// Wait until at least one of `printers` isn't paused.
+    pub async fn wait_any(&self, printers: &[String]) {
+        let mut paused = self.paused.subscribe();
+        // The sender lives as long as the app, so this can't fail
+        let _ = paused
+            .wait_for(|set| printers.iter().any(|p| !set.contains(p)))
+            .await;
+    }
+
+    /// Take a place at the back of `printer`'s queue of held jobs.
+    pub fn hold(&self, printer: &str) -> Ticket<'_> {
+        let id = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        self.queues.send_modify(|queues| {
+            queues.entry(printer.to_string()).or_default().push_back(id);
+        });
+        Ticket {
+            holds: self,
+            printer: printer.to_string(),
+            id,
+        }
+    }
+
+    /// Whether jobs are held for `printer`, which new jobs have to queue
+    /// behind even once it's resumed.
+    pub fn is_queued(&self, printer: &str) -> bool {
+        self.queues.borrow().contains_key(printer)
+    }
+
+    /// Wait until every job held before `ticket` has given up its place.
+    pub async fn wait_turn(&self, ticket: &Ticket<'_>) {
+        let mut queues = self.queues.subscribe();
+        let _ = queues
+            .wait_for(|queues| {
+                queues.get(&ticket.printer).and_then(VecDeque::front) == Some(&ticket.id)
+            })
+            .await;
+    }
+
+    /// Fill in `paused` on listed printers. An alias is paused when the
+    /// printer it points at is.
+    pub fn mark(&self, printers: &mut [Printer]) {
+        let set = self.paused.borrow();
+        for printer in printers {
+            let target = printer.alias_for.as_ref().unwrap_or(&printer.name);
+            printer.paused = set.contains(target);
+        }
+    }
+}
+
+/// The printer or pool `name` resolves to, if jobs can be sent to it. A
+/// paused printer always resolves, so one that's gone can still be resumed.
+/// Blocks on discovery, so call it off the async runtime.
+pub fn resolve(state: &AppState, name: &str) -> Result<String, Error> {
+    let (printer, is_pool, accepted) = {
+        let config = state.config.read()?;
+        let printer = config.resolve_printer(name);
+        let is_pool = config.pools.contains_key(&printer);
+        (printer, is_pool, config.accepts_printer(name))
+    };
+    if is_pool
+        || (accepted && (printer.starts_with("tcp://") || peers::is_peer(&printer)))
+        || state.holds.is_paused(&printer)
+        || metrics::discover(state)?.iter().any(|p| p.name == printer)
+    {
+        Ok(printer)
+    } else {
+        Err(Error::new(
+            ErrorCode::PrinterNotFound,
+            format!("Printer {printer} does not exist"),
+        ))
+    }
+}
+
+/// Pause or resume `printer` (already resolved), telling the UI.
+pub fn set_paused(state: &AppState, printer: &str, paused: bool) -> PrinterPaused {
+    if state.holds.set_paused(printer, paused) {
+        if paused {
+            log::info!("Paused {printer}, holding new jobs");
+        } else {
+            log::info!("Resumed {printer}");
+        }
+    }
+    let event = PrinterPaused {
+        printer: printer.to_string(),
+        paused,
+    };
+    state.app_handle.emit("printer-paused", &event).ok();
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn held_until_resumed() {
+        let holds = Arc::new(Holds::default());
+        assert!(holds.set_paused("Zebra", true));
+        assert!(!holds.set_paused("Zebra", true));
+
+        let waiting = {
+            let holds = holds.clone();
+            tokio::spawn(async move { holds.wait_any(&names(&["Zebra"])).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        holds.set_paused("Zebra", false);
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn one_free_printer_is_enough() {
+        let holds = Holds::default();
+        holds.set_paused("A", true);
+        tokio::time::timeout(Duration::from_secs(1), holds.wait_any(&names(&["A", "B"])))
+            .await
+            .unwrap();
+    }
+
+    /// A held job: prints once the printer is resumed and it's its turn,
+    /// then gives up its place.
+    async fn release(holds: &Holds, ticket: Ticket<'_>, n: usize, printed: &Mutex<Vec<usize>>) {
+        holds.wait_any(&names(&["Zebra"])).await;
+        holds.wait_turn(&ticket).await;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        printed.lock().unwrap().push(n);
+    }
+
+    #[tokio::test]
+    async fn held_jobs_are_released_in_order() {
+        let holds = Holds::default();
+        holds.set_paused("Zebra", true);
+        let [first, second, third] = [(); 3].map(|_| holds.hold("Zebra"));
+        assert!(holds.is_queued("Zebra"));
+        let printed = Mutex::new(Vec::new());
+
+        let resume = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            holds.set_paused("Zebra", false);
+        };
+        // The last job is polled first
+        let released = async {
+            tokio::join!(
+                release(&holds, third, 3, &printed),
+                release(&holds, second, 2, &printed),
+                release(&holds, first, 1, &printed),
+                resume,
+            )
+        };
+        tokio::time::timeout(Duration::from_secs(1), released)
+            .await
+            .unwrap();
+
+        assert_eq!(*printed.lock().unwrap(), [1, 2, 3]);
+        assert!(!holds.is_queued("Zebra"));
+    }
+
+    #[test]
+    fn aliases_show_their_printer_paused() {
+        let holds = Holds::default();
+        holds.set_paused("Zebra", true);
+        let printer = |name: &str, alias_for: Option<&str>| Printer {
+            name: name.to_string(),
+            is_default: false,
+            alias_for: alias_for.map(String::from),
+            paused: false,
+        };
+        let mut printers = vec![
+            printer("Zebra", None),
+            printer("Office", None),
+            printer("labels", Some("Zebra")),
+        ];
+        holds.mark(&mut printers);
+        let paused: Vec<bool> = printers.iter().map(|p| p.paused).collect();
+        assert_eq!(paused, [true, false, true]);
+    }
+}
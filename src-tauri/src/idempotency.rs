@@ -0,0 +1,158 @@
+//! `Idempotency-Key` support for `POST /print`, so a client retrying over
+//! flaky Wi-Fi gets the first attempt's result instead of a second label.
+//!
+//! Keys are stored on the jobs in the history, so a key is remembered for as
+//! long as its jobs are kept and the configured window hasn't passed. Requests
+//! with the same key are handled one at a time, so a retry that arrives while
+//! the original is still printing waits for it and then replays its result.
+
+use crate::jobs::PrintJob;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OwnedMutexGuard;
+
+pub const HEADER: &str = "Idempotency-Key";
+/// Set on responses that replay an earlier request's result.
+pub const REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// The jobs an earlier request with `key` created, if it was at most
+/// `window` seconds before `now`. Oldest first.
+pub fn previous(history: &[PrintJob], key: &str, window: u64, now: u64) -> Vec<PrintJob> {
+    let mut jobs: Vec<PrintJob> = history
+        .iter()
+        .filter(|job| job.idempotency_key.as_deref() == Some(key))
+        .filter(|job| now.saturating_sub(job.timestamp) <= window)
+        .cloned()
+        .collect();
+
+    // A fan-out request leaves one job per printer, all with the same parent.
+    // Only the latest request counts if the key was reused after the window.
+    match jobs.first().map(|latest| latest.parent_id.clone()) {
+        Some(Some(parent)) => jobs.retain(|job| job.parent_id.as_deref() == Some(&parent)),
+        Some(None) => jobs.truncate(1),
+        None => {}
+    }
+    jobs.sort_by(|a, b| a.id.cmp(&b.id));
+    jobs
+}
+
+/// One lock per key in use, so requests sharing a key run one at a time.
+#[derive(Default)]
+pub struct KeyLocks {
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+/// Held while a request with a key is being handled.
+pub struct KeyGuard<'a> {
+    locks: &'a KeyLocks,
+    key: String,
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl KeyLocks {
+    /// Wait until no other request with `key` is in progress.
+    pub async fn lock(&self, key: &str) -> KeyGuard<'_> {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+            locks.entry(key.to_string()).or_default().clone()
+        };
+        KeyGuard {
+            locks: self,
+            key: key.to_string(),
+            _guard: lock.lock_owned().await,
+        }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.locks.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+impl Drop for KeyGuard<'_> {
+    fn drop(&mut self) {
+        let mut locks = self.locks.locks.lock().unwrap_or_else(|e| e.into_inner());
+        // Only the map and this guard hold the lock: nobody else is waiting
+        if locks
+            .get(&self.key)
+            .is_some_and(|lock| Arc::strong_count(lock) == 2)
+        {
+            locks.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::JobStatus;
+    use std::time::Duration;
+
+    fn job(id: &str, key: Option<&str>, timestamp: u64, parent_id: Option<&str>) -> PrintJob {
+        PrintJob {
+            id: id.to_string(),
+            printer: "ZebraRaw".to_string(),
+            timestamp,
+            status: JobStatus::Completed,
+            zpl_preview: None,
+            error: None,
+            error_code: None,
+            dispatched_to: None,
+            rule: None,
+            parent_id: parent_id.map(str::to_string),
+            remote_job_id: None,
+            idempotency_key: key.map(str::to_string),
+            scheduled_for: None,
+            metadata: Default::default(),
+        }
+    }
+
+    fn ids(jobs: &[PrintJob]) -> Vec<&str> {
+        jobs.iter().map(|j| j.id.as_str()).collect()
+    }
+
+    #[test]
+    fn finds_the_job_for_a_key_within_the_window() {
+        // Newest first, like the history
+        let history = [
+            job("c", Some("order-2"), 1_000, None),
+            job("b", None, 990, None),
+            job("a", Some("order-1"), 900, None),
+        ];
+
+        assert_eq!(ids(&previous(&history, "order-1", 300, 1_000)), ["a"]);
+        assert!(previous(&history, "order-1", 60, 1_000).is_empty());
+        assert!(previous(&history, "order-3", 300, 1_000).is_empty());
+    }
+
+    #[test]
+    fn fan_out_jobs_come_back_together() {
+        let history = [
+            job("b2", Some("k"), 1_000, Some("p2")),
+            job("b1", Some("k"), 1_000, Some("p2")),
+            job("a1", Some("k"), 100, Some("p1")),
+        ];
+
+        assert_eq!(ids(&previous(&history, "k", 5_000, 1_000)), ["b1", "b2"]);
+    }
+
+    #[tokio::test]
+    async fn same_key_waits_for_the_first_request() {
+        let locks = Arc::new(KeyLocks::default());
+        let first = locks.lock("k").await;
+        let _other = locks.lock("other").await;
+
+        let waiter = {
+            let locks = locks.clone();
+            tokio::spawn(async move {
+                let _guard = locks.lock("k").await;
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        drop(first);
+        waiter.await.unwrap();
+        assert_eq!(locks.len(), 1);
+    }
+}
@@ -0,0 +1,710 @@
+//! Just enough of IPP (RFC 8010/8011) for driverless printing to a Dazzle
+//! printer at `/ipp/print/{name}`.
+//!
+//! Each printer Dazzle can reach looks like a raw ZPL printer: it answers
+//! Get-Printer-Attributes, and Print-Job sends the document through
+//! [`jobs::run`] so it's tracked like any other job. Get-Jobs and
+//! Get-Job-Attributes read the job history. Print-Job returns once the job
+//! has printed; a job for a paused printer is listed as pending-held until
+//! then. A paused printer reports itself stopped, and while all printing is
+//! paused it isn't accepting jobs. Jobs can't be cancelled over IPP.
+
+use crate::jobs::{self, JobStatus, PrintJob};
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use std::time::Instant;
+
+const CONTENT_TYPE: &str = "application/ipp";
+
+// Operations
+const PRINT_JOB: u16 = 0x0002;
+const VALIDATE_JOB: u16 = 0x0004;
+const GET_JOB_ATTRIBUTES: u16 = 0x0009;
+const GET_JOBS: u16 = 0x000A;
+const GET_PRINTER_ATTRIBUTES: u16 = 0x000B;
+
+// Status codes
+const SUCCESSFUL_OK: u16 = 0x0000;
+const CLIENT_ERROR_BAD_REQUEST: u16 = 0x0400;
+const CLIENT_ERROR_NOT_FOUND: u16 = 0x0406;
+const CLIENT_ERROR_DOCUMENT_FORMAT_NOT_SUPPORTED: u16 = 0x040A;
+const SERVER_ERROR_INTERNAL_ERROR: u16 = 0x0500;
+const SERVER_ERROR_OPERATION_NOT_SUPPORTED: u16 = 0x0501;
+
+// Delimiter tags
+const OPERATION_ATTRIBUTES: u8 = 0x01;
+const JOB_ATTRIBUTES: u8 = 0x02;
+const END_OF_ATTRIBUTES: u8 = 0x03;
+const PRINTER_ATTRIBUTES: u8 = 0x04;
+
+// Value tags
+const INTEGER: u8 = 0x21;
+const BOOLEAN: u8 = 0x22;
+const ENUM: u8 = 0x23;
+const TEXT: u8 = 0x41;
+const NAME: u8 = 0x42;
+const KEYWORD: u8 = 0x44;
+const URI: u8 = 0x45;
+const CHARSET: u8 = 0x47;
+const NATURAL_LANGUAGE: u8 = 0x48;
+const MIME_MEDIA_TYPE: u8 = 0x49;
+
+// printer-state values
+const PRINTER_IDLE: i32 = 3;
+const PRINTER_STOPPED: i32 = 5;
+
+// job-state values
+const JOB_PENDING_HELD: i32 = 4;
+const JOB_PROCESSING: i32 = 5;
+const JOB_ABORTED: i32 = 8;
+const JOB_COMPLETED: i32 = 9;
+
+const DOCUMENT_FORMATS: [&str; 2] = ["application/vnd.zebra-zpl", "application/octet-stream"];
+
+/// A decoded IPP request: header, attribute groups and the trailing document.
+#[derive(Debug)]
+struct Request {
+    operation: u16,
+    request_id: u32,
+    groups: Vec<(u8, Vec<Attribute>)>,
+    data: Bytes,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Attribute {
+    name: String,
+    values: Vec<(u8, Vec<u8>)>,
+}
+
+impl Attribute {
+    fn new(tag: u8, name: &str, value: impl Into<Vec<u8>>) -> Self {
+        Self {
+            name: name.to_string(),
+            values: vec![(tag, value.into())],
+        }
+    }
+
+    fn strings(tag: u8, name: &str, values: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            values: values
+                .iter()
+                .map(|v| (tag, v.as_bytes().to_vec()))
+                .collect(),
+        }
+    }
+
+    fn integer(tag: u8, name: &str, value: i32) -> Self {
+        Self::new(tag, name, value.to_be_bytes())
+    }
+
+    fn integers(tag: u8, name: &str, values: &[i32]) -> Self {
+        Self {
+            name: name.to_string(),
+            values: values
+                .iter()
+                .map(|v| (tag, v.to_be_bytes().to_vec()))
+                .collect(),
+        }
+    }
+
+    fn boolean(name: &str, value: bool) -> Self {
+        Self::new(BOOLEAN, name, [u8::from(value)])
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.values.first()?.1).ok()
+    }
+
+    fn as_i32(&self) -> Option<i32> {
+        let bytes: [u8; 4] = self.values.first()?.1.as_slice().try_into().ok()?;
+        Some(i32::from_be_bytes(bytes))
+    }
+}
+
+impl Request {
+    fn decode(body: Bytes) -> Result<Self, String> {
+        let mut pos = 0;
+        let header = body.get(..8).ok_or("Truncated IPP header")?;
+        let operation = u16::from_be_bytes([header[2], header[3]]);
+        let request_id = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        pos += 8;
+
+        let mut groups: Vec<(u8, Vec<Attribute>)> = Vec::new();
+        loop {
+            let tag = *body.get(pos).ok_or("Missing end-of-attributes tag")?;
+            pos += 1;
+
+            if tag == END_OF_ATTRIBUTES {
+                break;
+            }
+            if tag < 0x10 {
+                // A new attribute group
+                groups.push((tag, Vec::new()));
+                continue;
+            }
+
+            let name_len = read_u16(&body, pos)? as usize;
+            let name = body
+                .get(pos + 2..pos + 2 + name_len)
+                .ok_or("Truncated attribute name")?;
+            pos += 2 + name_len;
+            let value_len = read_u16(&body, pos)? as usize;
+            let value = body
+                .get(pos + 2..pos + 2 + value_len)
+                .ok_or("Truncated attribute value")?
+                .to_vec();
+            pos += 2 + value_len;
+
+            let (_, attributes) = groups.last_mut().ok_or("Attribute outside of a group")?;
+            if name.is_empty() {
+                // An additional value of the previous attribute
+                attributes
+                    .last_mut()
+                    .ok_or("Additional value without an attribute")?
+                    .values
+                    .push((tag, value));
+            } else {
+                attributes.push(Attribute {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    values: vec![(tag, value)],
+                });
+            }
+        }
+
+        Ok(Self {
+            operation,
+            request_id,
+            groups,
+            data: body.slice(pos..),
+        })
+    }
+
+    fn operation_attribute(&self, name: &str) -> Option<&Attribute> {
+        self.groups
+            .iter()
+            .filter(|(tag, _)| *tag == OPERATION_ATTRIBUTES)
+            .flat_map(|(_, attributes)| attributes)
+            .find(|a| a.name == name)
+    }
+
+    /// Names listed in `requested-attributes`, or `None` for "everything".
+    fn requested_attributes(&self) -> Option<Vec<String>> {
+        let requested: Vec<String> = self
+            .operation_attribute("requested-attributes")?
+            .values
+            .iter()
+            .map(|(_, v)| String::from_utf8_lossy(v).into_owned())
+            .collect();
+        let everything = requested
+            .iter()
+            .any(|r| r == "all" || r == "printer-description" || r == "job-description");
+        (!everything).then_some(requested)
+    }
+}
+
+fn read_u16(body: &[u8], pos: usize) -> Result<u16, String> {
+    body.get(pos..pos + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| "Truncated attribute".to_string())
+}
+
+/// An IPP response under construction.
+struct Reply {
+    status: u16,
+    request_id: u32,
+    groups: Vec<(u8, Vec<Attribute>)>,
+}
+
+impl Reply {
+    fn new(status: u16, request_id: u32) -> Self {
+        let operation = vec![
+            Attribute::new(CHARSET, "attributes-charset", "utf-8"),
+            Attribute::new(NATURAL_LANGUAGE, "attributes-natural-language", "en"),
+        ];
+        Self {
+            status,
+            request_id,
+            groups: vec![(OPERATION_ATTRIBUTES, operation)],
+        }
+    }
+
+    fn error(status: u16, request_id: u32, message: &str) -> Self {
+        let mut reply = Self::new(status, request_id);
+        reply.groups[0]
+            .1
+            .push(Attribute::new(TEXT, "status-message", message));
+        reply
+    }
+
+    fn group(mut self, tag: u8, attributes: Vec<Attribute>) -> Self {
+        self.groups.push((tag, attributes));
+        self
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = vec![2, 0];
+        out.extend(self.status.to_be_bytes());
+        out.extend(self.request_id.to_be_bytes());
+        for (tag, attributes) in &self.groups {
+            out.push(*tag);
+            for attribute in attributes {
+                for (i, (value_tag, value)) in attribute.values.iter().enumerate() {
+                    let name = if i == 0 {
+                        attribute.name.as_bytes()
+                    } else {
+                        &[]
+                    };
+                    out.push(*value_tag);
+                    out.extend((name.len() as u16).to_be_bytes());
+                    out.extend(name);
+                    out.extend((value.len() as u16).to_be_bytes());
+                    out.extend(value);
+                }
+            }
+        }
+        out.push(END_OF_ATTRIBUTES);
+        out
+    }
+}
+
+impl IntoResponse for Reply {
+    fn into_response(self) -> Response {
+        // IPP errors travel in the IPP status code; HTTP is always 200
+        (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, CONTENT_TYPE)],
+            self.encode(),
+        )
+            .into_response()
+    }
+}
+
+/// `POST /ipp/print/{name}`
+pub async fn handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let request = match Request::decode(body) {
+        Ok(request) => request,
+        Err(e) => {
+            log::warn!("Bad IPP request for {name}: {e}");
+            return (StatusCode::BAD_REQUEST, e).into_response();
+        }
+    };
+
+    let host = headers
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("localhost");
+    let printer_uri = format!("ipp://{host}/ipp/print/{}", encode_path(&name));
+
+    let reply = match request.operation {
+        GET_PRINTER_ATTRIBUTES => printer_attributes(&state, &request, &name, &printer_uri),
+        VALIDATE_JOB => match check_format(&request) {
+            Ok(()) => Reply::new(SUCCESSFUL_OK, request.request_id),
+            Err(reply) => reply,
+        },
+        PRINT_JOB => print_job(&state, request, name, &printer_uri).await,
+        GET_JOBS => get_jobs(&state, &request, &name, &printer_uri),
+        GET_JOB_ATTRIBUTES => get_job_attributes(&state, &request, &printer_uri),
+        other => Reply::error(
+            SERVER_ERROR_OPERATION_NOT_SUPPORTED,
+            request.request_id,
+            &format!("Operation {other:#06x} is not supported"),
+        ),
+    };
+
+    reply.into_response()
+}
+
+/// What clients on the LAN must authenticate with: the token as the Basic
+/// auth password once LAN sharing has one (see [`crate::lan`]).
+fn authentication_supported(lan: &crate::config::LanConfig) -> &'static str {
+    if lan.enabled && lan.token.as_deref().is_some_and(|t| !t.is_empty()) {
+        "basic"
+    } else {
+        "none"
+    }
+}
+
+fn printer_attributes(
+    state: &crate::AppState,
+    request: &Request,
+    name: &str,
+    printer_uri: &str,
+) -> Reply {
+    let (authentication, printer) = match state.config.read() {
+        Ok(config) => (
+            authentication_supported(&config.lan),
+            config.resolve_printer(name),
+        ),
+        Err(_) => ("none", name.to_string()),
+    };
+    // The history has the printer an alias points at
+    let queued = state
+        .print_jobs
+        .read()
+        .map(|jobs| {
+            jobs.iter()
+                .filter(|j| j.printer == printer)
+                .filter(|j| matches!(j.status, JobStatus::Printing | JobStatus::Held))
+                .count()
+        })
+        .unwrap_or(0);
+    let all_paused = state.limits.is_paused();
+    let (printer_state, reasons) = if all_paused || state.holds.is_paused(&printer) {
+        (PRINTER_STOPPED, "paused")
+    } else {
+        (PRINTER_IDLE, "none")
+    };
+
+    let attributes = vec![
+        Attribute::new(URI, "printer-uri-supported", printer_uri),
+        Attribute::new(KEYWORD, "uri-security-supported", "none"),
+        Attribute::new(KEYWORD, "uri-authentication-supported", authentication),
+        Attribute::new(NAME, "printer-name", name),
+        Attribute::new(TEXT, "printer-info", name),
+        Attribute::new(TEXT, "printer-make-and-model", "Dazzle ZPL Printer"),
+        Attribute::integer(ENUM, "printer-state", printer_state),
+        Attribute::new(KEYWORD, "printer-state-reasons", reasons),
+        // A paused printer still takes jobs and holds them
+        Attribute::boolean("printer-is-accepting-jobs", !all_paused),
+        Attribute::integer(INTEGER, "queued-job-count", queued as i32),
+        Attribute::strings(KEYWORD, "ipp-versions-supported", &["1.1", "2.0"]),
+        Attribute::integers(
+            ENUM,
+            "operations-supported",
+            &[
+                PRINT_JOB as i32,
+                VALIDATE_JOB as i32,
+                GET_JOB_ATTRIBUTES as i32,
+                GET_JOBS as i32,
+                GET_PRINTER_ATTRIBUTES as i32,
+            ],
+        ),
+        Attribute::new(CHARSET, "charset-configured", "utf-8"),
+        Attribute::new(CHARSET, "charset-supported", "utf-8"),
+        Attribute::new(NATURAL_LANGUAGE, "natural-language-configured", "en"),
+        Attribute::new(
+            NATURAL_LANGUAGE,
+            "generated-natural-language-supported",
+            "en",
+        ),
+        Attribute::new(
+            MIME_MEDIA_TYPE,
+            "document-format-default",
+            DOCUMENT_FORMATS[1],
+        ),
+        Attribute::strings(
+            MIME_MEDIA_TYPE,
+            "document-format-supported",
+            &DOCUMENT_FORMATS,
+        ),
+        Attribute::new(KEYWORD, "compression-supported", "none"),
+        Attribute::new(KEYWORD, "pdl-override-supported", "not-attempted"),
+        Attribute::integer(INTEGER, "printer-up-time", up_time()),
+    ];
+
+    let attributes = match request.requested_attributes() {
+        Some(requested) => attributes
+            .into_iter()
+            .filter(|a| requested.contains(&a.name))
+            .collect(),
+        None => attributes,
+    };
+
+    Reply::new(SUCCESSFUL_OK, request.request_id).group(PRINTER_ATTRIBUTES, attributes)
+}
+
+/// Refuse documents that aren't raw printer language.
+fn check_format(request: &Request) -> Result<(), Reply> {
+    match request
+        .operation_attribute("document-format")
+        .and_then(Attribute::as_str)
+    {
+        Some(format) if !DOCUMENT_FORMATS.contains(&format) => Err(Reply::error(
+            CLIENT_ERROR_DOCUMENT_FORMAT_NOT_SUPPORTED,
+            request.request_id,
+            &format!("Document format {format} is not supported"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+async fn print_job(
+    state: &Arc<crate::AppState>,
+    request: Request,
+    name: String,
+    printer_uri: &str,
+) -> Reply {
+    if let Err(reply) = check_format(&request) {
+        return reply;
+    }
+    if request.data.is_empty() {
+        return Reply::error(
+            CLIENT_ERROR_BAD_REQUEST,
+            request.request_id,
+            "Empty document",
+        );
+    }
+
+    let job = match jobs::run(state, name, request.data.to_vec()).await {
+        Ok(job) => job,
+        Err(e) => {
+            return Reply::error(
+                SERVER_ERROR_INTERNAL_ERROR,
+                request.request_id,
+                &e.to_string(),
+            );
+        }
+    };
+
+    match job.status {
+        JobStatus::Failed => Reply::error(
+            SERVER_ERROR_INTERNAL_ERROR,
+            request.request_id,
+            job.error.as_deref().unwrap_or("Print failed"),
+        ),
+        _ => Reply::new(SUCCESSFUL_OK, request.request_id)
+            .group(JOB_ATTRIBUTES, job_attributes(&job, printer_uri)),
+    }
+}
+
+fn get_jobs(state: &crate::AppState, request: &Request, name: &str, printer_uri: &str) -> Reply {
+    let jobs: Vec<PrintJob> = state
+        .print_jobs
+        .read()
+        .map(|jobs| jobs.iter().filter(|j| j.printer == name).cloned().collect())
+        .unwrap_or_default();
+
+    jobs.iter().fold(
+        Reply::new(SUCCESSFUL_OK, request.request_id),
+        |reply, job| reply.group(JOB_ATTRIBUTES, job_attributes(job, printer_uri)),
+    )
+}
+
+fn get_job_attributes(state: &crate::AppState, request: &Request, printer_uri: &str) -> Reply {
+    let Some(job_id) = request
+        .operation_attribute("job-id")
+        .and_then(Attribute::as_i32)
+    else {
+        return Reply::error(
+            CLIENT_ERROR_BAD_REQUEST,
+            request.request_id,
+            "Missing job-id",
+        );
+    };
+
+    let job = state
+        .print_jobs
+        .read()
+        .ok()
+        .and_then(|jobs| jobs.iter().find(|j| ipp_job_id(&j.id) == job_id).cloned());
+
+    match job {
+        Some(job) => Reply::new(SUCCESSFUL_OK, request.request_id)
+            .group(JOB_ATTRIBUTES, job_attributes(&job, printer_uri)),
+        None => Reply::error(CLIENT_ERROR_NOT_FOUND, request.request_id, "No such job"),
+    }
+}
+
+fn job_attributes(job: &PrintJob, printer_uri: &str) -> Vec<Attribute> {
+    let id = ipp_job_id(&job.id);
+    let (state, reason) = match job.status {
+        JobStatus::Scheduled | JobStatus::Held => (JOB_PENDING_HELD, "job-hold-until-specified"),
+        JobStatus::Printing => (JOB_PROCESSING, "job-printing"),
+        JobStatus::Completed => (JOB_COMPLETED, "job-completed-successfully"),
+        JobStatus::Failed => (JOB_ABORTED, "aborted-by-system"),
+    };
+
+    let mut attributes = vec![
+        Attribute::integer(INTEGER, "job-id", id),
+        Attribute::new(URI, "job-uri", format!("{printer_uri}/jobs/{id}")),
+        Attribute::new(URI, "job-printer-uri", printer_uri),
+        Attribute::integer(ENUM, "job-state", state),
+        Attribute::new(KEYWORD, "job-state-reasons", reason),
+    ];
+    if let Some(error) = &job.error {
+        attributes.push(Attribute::new(TEXT, "job-state-message", error.as_str()));
+    }
+    attributes
+}
+
+/// IPP job ids are positive 32-bit integers; ours are hex timestamps.
+fn ipp_job_id(id: &str) -> i32 {
+    let n = u64::from_str_radix(id, 16).unwrap_or(0);
+    (n % (i32::MAX as u64 - 1)) as i32 + 1
+}
+
+fn up_time() -> i32 {
+    static STARTED: std::sync::OnceLock<Instant> = std::sync::OnceLock::new();
+    STARTED.get_or_init(Instant::now).elapsed().as_secs() as i32 + 1
+}
+
+fn encode_path(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a request the way a client would, reusing the reply encoder.
+    fn request(operation: u16, attributes: Vec<Attribute>, data: &[u8]) -> Bytes {
+        let mut reply = Reply::new(operation, 7);
+        reply.groups[0].1.extend(attributes);
+        let mut bytes = reply.encode();
+        bytes[0] = 1;
+        bytes[1] = 1;
+        bytes.extend(data);
+        Bytes::from(bytes)
+    }
+
+    #[test]
+    fn decodes_header_attributes_and_document() {
+        let body = request(
+            PRINT_JOB,
+            vec![
+                Attribute::new(URI, "printer-uri", "ipp://localhost/ipp/print/Zebra"),
+                Attribute::new(
+                    MIME_MEDIA_TYPE,
+                    "document-format",
+                    "application/vnd.zebra-zpl",
+                ),
+            ],
+            b"^XA^XZ",
+        );
+
+        let request = Request::decode(body).unwrap();
+
+        assert_eq!(request.operation, PRINT_JOB);
+        assert_eq!(request.request_id, 7);
+        assert_eq!(
+            request
+                .operation_attribute("document-format")
+                .and_then(Attribute::as_str),
+            Some("application/vnd.zebra-zpl")
+        );
+        assert_eq!(&request.data[..], b"^XA^XZ");
+    }
+
+    #[test]
+    fn decodes_multi_valued_attributes() {
+        let body = request(
+            GET_PRINTER_ATTRIBUTES,
+            vec![Attribute::strings(
+                KEYWORD,
+                "requested-attributes",
+                &["printer-name", "printer-state"],
+            )],
+            b"",
+        );
+
+        let request = Request::decode(body).unwrap();
+
+        assert_eq!(
+            request.requested_attributes(),
+            Some(vec![
+                "printer-name".to_string(),
+                "printer-state".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn requesting_all_means_no_filter() {
+        let body = request(
+            GET_PRINTER_ATTRIBUTES,
+            vec![Attribute::strings(
+                KEYWORD,
+                "requested-attributes",
+                &["all"],
+            )],
+            b"",
+        );
+        assert_eq!(Request::decode(body).unwrap().requested_attributes(), None);
+    }
+
+    #[test]
+    fn truncated_requests_are_rejected() {
+        assert!(Request::decode(Bytes::from_static(b"\x02\x00\x00\x0b")).is_err());
+
+        let mut body = request(GET_PRINTER_ATTRIBUTES, vec![], b"").to_vec();
+        body.pop(); // drop end-of-attributes
+        assert!(Request::decode(Bytes::from(body)).is_err());
+    }
+
+    #[test]
+    fn unsupported_document_format_is_refused() {
+        let body = request(
+            PRINT_JOB,
+            vec![Attribute::new(
+                MIME_MEDIA_TYPE,
+                "document-format",
+                "application/pdf",
+            )],
+            b"%PDF",
+        );
+        let request = Request::decode(body).unwrap();
+
+        let reply = check_format(&request).err().unwrap();
+        assert_eq!(reply.status, CLIENT_ERROR_DOCUMENT_FORMAT_NOT_SUPPORTED);
+    }
+
+    #[test]
+    fn reply_encodes_status_and_groups() {
+        let reply = Reply::new(SUCCESSFUL_OK, 42).group(
+            JOB_ATTRIBUTES,
+            vec![Attribute::integer(INTEGER, "job-id", 5)],
+        );
+        let bytes = reply.encode();
+
+        assert_eq!(&bytes[..8], &[2, 0, 0, 0, 0, 0, 0, 42]);
+        assert_eq!(bytes.last(), Some(&END_OF_ATTRIBUTES));
+
+        // Our own decoder reads it back
+        let decoded = Request::decode(Bytes::from(bytes)).unwrap();
+        assert_eq!(decoded.groups.len(), 2);
+        assert_eq!(decoded.groups[1].1[0].as_i32(), Some(5));
+    }
+
+    #[test]
+    fn job_ids_are_positive_and_stable() {
+        let id = jobs::generate_id();
+        assert!(ipp_job_id(&id) > 0);
+        assert_eq!(ipp_job_id(&id), ipp_job_id(&id));
+        assert!(ipp_job_id("not-hex") > 0);
+    }
+
+    #[test]
+    fn advertises_basic_auth_with_a_lan_token() {
+        let mut lan = crate::config::LanConfig::default();
+        assert_eq!(authentication_supported(&lan), "none");
+        lan.enabled = true;
+        assert_eq!(authentication_supported(&lan), "none");
+        lan.token = Some("secret".into());
+        assert_eq!(authentication_supported(&lan), "basic");
+    }
+
+    #[test]
+    fn printer_names_are_escaped_in_uris() {
+        assert_eq!(encode_path("Zebra ZD420"), "Zebra%20ZD420");
+        assert_eq!(encode_path("Zebra_ZD420"), "Zebra_ZD420");
+    }
+}
@@ -0,0 +1,553 @@
+//! The print job pipeline shared by every way a job can reach Dazzle.
+//!
+//! Whether it arrived over HTTP or a raw socket, a job is announced with a
+//! `print-job` event, sent with [`printing::send_raw`], stored in the
+//! history and announced again with its outcome. Jobs for a paused printer
+//! wait in the history with [`JobStatus::Held`] until it's resumed, then
+//! print in the order they arrived. Jobs for a printer pool are tried on
+//! each member in turn until one prints, and jobs for `dazzle://` printers
+//! are forwarded to the station they're on with [`peers::forward`].
+//! Finished jobs are also handed to [`webhooks::notify`].
+
+use crate::config::PoolConfig;
+use crate::error::{Error, ErrorCode};
+use crate::{metrics, peers, printing, webhooks, AppState};
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::Emitter;
+use utoipa::ToSchema;
+
+/// Largest payload accepted for a single job, from any listener.
+pub const MAX_JOB_SIZE: usize = 10 * 1024 * 1024; // 10 MB
+
+/// How many finished jobs are kept in the history.
+const HISTORY_LEN: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PrintJob {
+    pub id: String,
+    pub printer: String,
+    pub timestamp: u64,
+    pub status: JobStatus,
+    pub zpl_preview: Option<String>,
+    pub error: Option<String>,
+    /// What kind of failure `error` describes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    /// For pool jobs, the member printer the job was printed on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dispatched_to: Option<String>,
+    /// The routing rule that picked the printer, if one did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    /// Shared by the jobs created when one request printed on several printers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    /// For jobs forwarded to another station, the job's id over there.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_job_id: Option<String>,
+    /// The `Idempotency-Key` of the request that created the job.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    /// For scheduled jobs, when the job is due to print, in seconds since the
+    /// Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheduled_for: Option<u64>,
+    #[serde(flatten)]
+    pub metadata: JobMetadata,
+}
+
+/// What a client said about a job, and where the request came from.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct JobMetadata {
+    /// The client's own id for the label, such as an order or shipment number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// The app or integration that sent the job.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Who printed it, as far as the client knows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// The request's `Origin` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// The request's `User-Agent` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+}
+
+/// Longest metadata value kept, in characters.
+const MAX_METADATA_LEN: usize = 256;
+const MAX_TAGS: usize = 20;
+
+impl JobMetadata {
+    /// Read `reference`, `tags`, `source` and `user` from the query string, or
+    /// else from the matching `X-Dazzle-*` header, plus `Origin` and
+    /// `User-Agent`. `tags` is comma-separated and may be repeated.
+    pub fn from_request(headers: &HeaderMap, query: &[(String, String)]) -> Self {
+        let header = |name: &str| {
+            headers
+                .get_all(name)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        let param = |name: &str| {
+            let values: Vec<String> = query
+                .iter()
+                .filter(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+                .collect();
+            if values.is_empty() {
+                header(&format!("X-Dazzle-{name}"))
+            } else {
+                values
+            }
+        };
+        let first = |values: Vec<String>| values.into_iter().find_map(clean);
+
+        let mut tags: Vec<String> = Vec::new();
+        for tag in param("tags").iter().flat_map(|v| v.split(',')) {
+            if let Some(tag) = clean(tag.to_string()) {
+                if !tags.contains(&tag) && tags.len() < MAX_TAGS {
+                    tags.push(tag);
+                }
+            }
+        }
+
+        Self {
+            reference: first(param("reference")),
+            tags,
+            source: first(param("source")),
+            user: first(param("user")),
+            origin: first(header("Origin")),
+            user_agent: first(header("User-Agent")),
+        }
+    }
+}
+
+/// Trim `value`, dropping it if empty and shortening it if too long.
+fn clean(value: String) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    Some(value.chars().take(MAX_METADATA_LEN).collect())
+}
+
+impl PrintJob {
+    /// Whether every whitespace-separated term in `search` appears in one of
+    /// the job's ids, printer, metadata, preview or error, ignoring case.
+    pub fn matches(&self, search: &str) -> bool {
+        let metadata = &self.metadata;
+        let fields: Vec<String> = [
+            Some(&self.id),
+            Some(&self.printer),
+            self.dispatched_to.as_ref(),
+            self.rule.as_ref(),
+            self.parent_id.as_ref(),
+            self.remote_job_id.as_ref(),
+            metadata.reference.as_ref(),
+            metadata.source.as_ref(),
+            metadata.user.as_ref(),
+            metadata.origin.as_ref(),
+            metadata.user_agent.as_ref(),
+            self.zpl_preview.as_ref(),
+            self.error.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(&metadata.tags)
+        .map(|field| field.to_lowercase())
+        .collect();
+
+        search.split_whitespace().all(|term| {
+            let term = term.to_lowercase();
+            fields.iter().any(|field| field.contains(&term))
+        })
+    }
+}
+
+/// Where a job came from and how it was routed, recorded on the job.
+#[derive(Debug, Clone, Default)]
+pub struct JobContext {
+    /// Id for the job, when the caller needs to know it up front.
+    pub id: Option<String>,
+    pub rule: Option<String>,
+    pub parent_id: Option<String>,
+    /// Extra URL to send the finished job to, besides the configured webhooks.
+    pub callback: Option<String>,
+    /// The job was forwarded here by another station, so it must not be
+    /// forwarded again.
+    pub forwarded: bool,
+    pub idempotency_key: Option<String>,
+    /// When a scheduled job was due to print.
+    pub scheduled_for: Option<u64>,
+    pub metadata: JobMetadata,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// Waiting for the time it was scheduled for.
+    Scheduled,
+    /// Waiting for its printer to be resumed.
+    Held,
+    Printing,
+    Completed,
+    Failed,
+}
+
+/// A new job id: nanoseconds since the Unix epoch in hex, bumped past the
+/// last id handed out so jobs created in the same tick still differ.
+pub fn generate_id() -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static LAST: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64;
+    let next = |last: u64| nanos.max(last + 1);
+    // The closure always returns Some, so this can't fail
+    let last = LAST
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+            Some(next(last))
+        })
+        .unwrap_or_default();
+    format!("{:x}", next(last))
+}
+
+pub fn now_secs() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Print `data` on `printer` as a tracked job and return the finished job.
+/// `printer` may be an alias; the job records the printer it resolved to.
+/// When it names a pool, each member is tried until one succeeds.
+///
+/// A failed print is not an error here: it comes back as a job with
+/// [`JobStatus::Failed`] so callers can report it however their protocol
+/// wants. `Err` means the job was refused before it started, because
+/// printing is paused, too many jobs are in progress or `printer` is a
+/// network address that isn't configured, or that the print task itself
+/// died.
+pub async fn run(state: &Arc<AppState>, printer: String, data: Vec<u8>) -> Result<PrintJob, Error> {
+    run_with_context(state, printer, data, JobContext::default()).await
+}
+
+/// [`run`], recording `context` on the job.
+pub async fn run_with_context(
+    state: &Arc<AppState>,
+    printer: String,
+    data: Vec<u8>,
+    context: JobContext,
+) -> Result<PrintJob, Error> {
+    // A held job keeps its place even if the client that sent it goes away
+    let state = state.clone();
+    tokio::spawn(async move { execute(&state, printer, data, context).await }).await?
+}
+
+async fn execute(
+    state: &Arc<AppState>,
+    printer: String,
+    data: Vec<u8>,
+    context: JobContext,
+) -> Result<PrintJob, Error> {
+    let max_jobs = {
+        let config = state.config.read()?;
+        if !config.accepts_printer(&printer) {
+            return Err(Error::new(
+                ErrorCode::InvalidRequest,
+                format!("{printer} isn't configured, add it as an alias to print to it"),
+            ));
+        }
+        config.limits.max_jobs
+    };
+    let _slot = state.limits.start_job(max_jobs)?;
+
+    let (printer, pool, peer_tokens) = match state.config.read() {
+        Ok(config) => {
+            let printer = config.resolve_printer(&printer);
+            let pool = config.pools.get(&printer).map(|pool| {
+                // Sends are tracked by the printer they went to, so rank
+                // members by that rather than by their alias
+                let resolved = PoolConfig {
+                    members: pool
+                        .members
+                        .iter()
+                        .map(|member| config.resolve_printer(member))
+                        .collect(),
+                    strategy: pool.strategy,
+                };
+                state.pools.candidates(&printer, &resolved)
+            });
+            (printer, pool, config.peer_tokens.clone())
+        }
+        Err(_) => (printer, None, Default::default()),
+    };
+
+    let zpl_preview = String::from_utf8_lossy(&data[..data.len().min(200)]).to_string();
+
+    let mut job = PrintJob {
+        id: context.id.unwrap_or_else(generate_id),
+        printer: printer.clone(),
+        timestamp: now_secs(),
+        status: JobStatus::Printing,
+        zpl_preview: Some(zpl_preview),
+        error: None,
+        error_code: None,
+        dispatched_to: None,
+        rule: context.rule,
+        parent_id: context.parent_id,
+        remote_job_id: None,
+        idempotency_key: context.idempotency_key,
+        scheduled_for: context.scheduled_for,
+        metadata: context.metadata,
+    };
+
+    let is_pool = pool.is_some();
+    let mut targets = pool.unwrap_or_else(|| vec![printer.clone()]);
+
+    let held = || {
+        state.holds.is_paused(&printer)
+            || (!targets.is_empty() && targets.iter().all(|t| state.holds.is_paused(t)))
+    };
+    // Jobs held for the printer print first, even once it's resumed
+    let mut place = None;
+    if held() || state.holds.is_queued(&printer) {
+        job.status = JobStatus::Held;
+        if let Ok(mut jobs) = state.print_jobs.write() {
+            jobs.insert(0, job.clone());
+            jobs.truncate(HISTORY_LEN);
+        }
+        state.app_handle.emit("print-job", &job).ok();
+        log::info!("Holding job {} for {printer}", job.id);
+        let ticket = place.insert(state.holds.hold(&printer));
+        // Printing may be paused as a whole, or the printer paused again,
+        // while the job waits, so check both until neither is
+        loop {
+            state.holds.wait_any(std::slice::from_ref(&printer)).await;
+            if !targets.is_empty() {
+                state.holds.wait_any(&targets).await;
+            }
+            state.limits.wait_resumed().await;
+            state.holds.wait_turn(ticket).await;
+            if !held() && !state.limits.is_paused() {
+                break;
+            }
+        }
+        job.status = JobStatus::Printing;
+    }
+    state.app_handle.emit("print-job", &job).ok();
+
+    // Paused pool members sit this job out
+    if is_pool {
+        let ready: Vec<String> = targets
+            .iter()
+            .filter(|t| !state.holds.is_paused(t))
+            .cloned()
+            .collect();
+        if !ready.is_empty() {
+            targets = ready;
+        }
+    }
+    let data = Arc::new(data);
+    let mut errors = Vec::new();
+
+    for target in targets {
+        state.pools.begin(&target);
+        let label = metrics::printer_label(state, &target);
+        state.metrics.send_started(label);
+        let started = std::time::Instant::now();
+
+        let print_result = if peers::is_peer(&target) {
+            if context.forwarded {
+                Ok(Err(Error::new(
+                    ErrorCode::PeerError,
+                    "Not forwarding a job that another station forwarded here",
+                )))
+            } else {
+                let forwarded = peers::forward(&target, &data, &peer_tokens).await;
+                job.remote_job_id = forwarded.job_id;
+                Ok(forwarded.result)
+            }
+        } else {
+            // Run blocking print operation off the async runtime
+            let target = target.clone();
+            let data = data.clone();
+            tokio::task::spawn_blocking(move || printing::send_raw(&target, &data)).await
+        };
+        let ok = matches!(print_result, Ok(Ok(())));
+        state.pools.finish(&target, ok);
+        state
+            .metrics
+            .send_finished(label, data.len(), started.elapsed(), ok);
+
+        match print_result? {
+            Ok(()) => {
+                job.status = JobStatus::Completed;
+                if is_pool {
+                    log::info!("Printed to {target} (pool {})", job.printer);
+                    job.dispatched_to = Some(target);
+                } else {
+                    log::info!("Printed to {}", job.printer);
+                }
+                break;
+            }
+            Err(e) if is_pool => {
+                log::warn!("Pool {} member {target} failed: {e}", job.printer);
+                errors.push(Error::new(
+                    e.code,
+                    format!("{target}: {}", e.to_string().trim()),
+                ));
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    if !matches!(job.status, JobStatus::Completed) {
+        let error = match errors.len() {
+            0 => Error::new(
+                ErrorCode::PrinterNotFound,
+                format!("Pool {} has no members", job.printer),
+            ),
+            1 => errors.remove(0),
+            // A pool failed on every member: report the last member's reason
+            _ => Error::new(
+                errors[errors.len() - 1].code,
+                errors
+                    .iter()
+                    .map(Error::to_string)
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            ),
+        };
+        log::error!("Print failed for {}: {error}", job.printer);
+        job.status = JobStatus::Failed;
+        job.error = Some(error.to_string());
+        job.error_code = Some(error.code);
+    }
+
+    record(state, &job, context.callback.as_deref());
+    Ok(job)
+}
+
+/// Store a finished job in the history, replacing it if it was held, and
+/// announce it to the UI, the metrics and the webhooks.
+pub fn record(state: &Arc<AppState>, job: &PrintJob, callback: Option<&str>) {
+    let label = metrics::printer_label(state, &job.printer);
+    state.metrics.job_finished(label, &job.status);
+
+    if let Ok(mut jobs) = state.print_jobs.write() {
+        jobs.retain(|j| j.id != job.id);
+        jobs.insert(0, job.clone());
+        jobs.truncate(HISTORY_LEN);
+    }
+
+    state.app_handle.emit("print-job", job).ok();
+    webhooks::notify(state, job, callback);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn ids_are_unique_across_threads() {
+        let threads: Vec<_> = (0..4)
+            .map(|_| std::thread::spawn(|| (0..1000).map(|_| generate_id()).collect::<Vec<_>>()))
+            .collect();
+        let ids: std::collections::HashSet<String> = threads
+            .into_iter()
+            .flat_map(|t| t.join().unwrap())
+            .collect();
+        assert_eq!(ids.len(), 4000);
+    }
+
+    #[test]
+    fn metadata_comes_from_query_or_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-dazzle-reference", "ORD-1".parse().unwrap());
+        headers.insert("x-dazzle-user", "sam".parse().unwrap());
+        headers.insert("origin", "https://shop.example.com".parse().unwrap());
+        headers.insert("user-agent", "Mozilla/5.0".parse().unwrap());
+
+        let metadata = JobMetadata::from_request(
+            &headers,
+            &query(&[
+                ("reference", "ORD-2"),
+                ("tags", "rush, fragile"),
+                ("tags", "rush"),
+                ("source", "  "),
+            ]),
+        );
+
+        assert_eq!(
+            metadata,
+            JobMetadata {
+                reference: Some("ORD-2".to_string()),
+                tags: vec!["rush".to_string(), "fragile".to_string()],
+                source: None,
+                user: Some("sam".to_string()),
+                origin: Some("https://shop.example.com".to_string()),
+                user_agent: Some("Mozilla/5.0".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn long_metadata_is_truncated() {
+        let long = "x".repeat(MAX_METADATA_LEN + 10);
+        let metadata = JobMetadata::from_request(&HeaderMap::new(), &query(&[("user", &long)]));
+        assert_eq!(metadata.user.unwrap().len(), MAX_METADATA_LEN);
+    }
+
+    #[test]
+    fn search_matches_all_terms_across_fields() {
+        let job = PrintJob {
+            id: "18c0ffee".to_string(),
+            printer: "Zebra_Shipping".to_string(),
+            timestamp: 0,
+            status: JobStatus::Completed,
+            zpl_preview: Some("^XA^FDHello^FS^XZ".to_string()),
+            error: None,
+            error_code: None,
+            dispatched_to: None,
+            rule: None,
+            parent_id: None,
+            remote_job_id: None,
+            idempotency_key: None,
+            scheduled_for: None,
+            metadata: JobMetadata {
+                reference: Some("SHIP-4471".to_string()),
+                tags: vec!["rush".to_string()],
+                user: Some("Sam".to_string()),
+                ..JobMetadata::default()
+            },
+        };
+
+        assert!(job.matches("ship-4471"));
+        assert!(job.matches("RUSH sam"));
+        assert!(job.matches("shipping hello"));
+        assert!(job.matches(""));
+        assert!(!job.matches("rush alex"));
+    }
+}
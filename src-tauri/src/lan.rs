@@ -0,0 +1,241 @@
+//! "Share on LAN" mode: access control for clients that aren't on this
+//! machine, and mDNS advertisement so tablets can find the station.
+//!
+//! Loopback clients are always let through untouched. Anyone else needs LAN
+//! sharing to be enabled, an address inside `allowed_networks` and the
+//! configured token, sent as a bearer token or as the Basic auth password.
+
+use crate::config::LanConfig;
+use crate::error::{Error, ErrorCode};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+const SERVICE_TYPE: &str = "_dazzle._tcp.local.";
+
+/// Axum middleware enforcing the LAN access rules on every request.
+pub async fn guard(
+    State(state): State<Arc<crate::AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let lan = match state.config.read() {
+        Ok(config) => config.lan.clone(),
+        Err(e) => return Error::from(e).into_response(),
+    };
+
+    let result = check_address(&lan, peer.ip()).and_then(|()| {
+        let token = request_token(request.headers());
+        check_token(&lan, peer.ip(), token.as_deref())
+    });
+
+    match result {
+        Ok(()) => next.run(request).await,
+        Err((status, message)) => {
+            log::warn!("Rejected request from {peer}: {message}");
+            if status == StatusCode::UNAUTHORIZED {
+                // Lets IPP clients and browsers prompt for the token
+                let challenge = [(header::WWW_AUTHENTICATE, "Basic realm=\"Dazzle\"")];
+                (challenge, Error::new(ErrorCode::Unauthorized, message)).into_response()
+            } else {
+                Error::new(ErrorCode::Forbidden, message).into_response()
+            }
+        }
+    }
+}
+
+/// Whether `peer` may connect at all. Used on its own by listeners that have
+/// no way to carry a token.
+pub fn check_address(lan: &LanConfig, peer: IpAddr) -> Result<(), (StatusCode, &'static str)> {
+    let peer = peer.to_canonical();
+    if peer.is_loopback() {
+        return Ok(());
+    }
+    if !lan.enabled {
+        return Err((StatusCode::FORBIDDEN, "LAN sharing is disabled"));
+    }
+    if !lan.allowed_networks.iter().any(|net| net.contains(&peer)) {
+        return Err((StatusCode::FORBIDDEN, "Client address is not allowed"));
+    }
+    Ok(())
+}
+
+fn check_token(
+    lan: &LanConfig,
+    peer: IpAddr,
+    given: Option<&str>,
+) -> Result<(), (StatusCode, &'static str)> {
+    if peer.to_canonical().is_loopback() {
+        return Ok(());
+    }
+    let Some(expected) = lan.token.as_deref().filter(|t| !t.is_empty()) else {
+        return Err((StatusCode::FORBIDDEN, "LAN sharing requires a token"));
+    };
+    match given {
+        Some(given) if tokens_match(expected, given) => Ok(()),
+        _ => Err((StatusCode::UNAUTHORIZED, "Missing or invalid token")),
+    }
+}
+
+/// The token from `Authorization: Bearer <token>`, or the password of HTTP
+/// Basic auth for clients (like IPP) that can only prompt for credentials.
+pub fn request_token(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+
+    if let Some(token) = value.strip_prefix("Bearer ") {
+        return Some(token.trim().to_string());
+    }
+
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    let credentials = String::from_utf8(decoded).ok()?;
+    let (_user, password) = credentials.split_once(':')?;
+    Some(password.to_string())
+}
+
+/// Constant-time comparison so the token can't be guessed byte by byte.
+fn tokens_match(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// A registered `_dazzle._tcp` mDNS service. Dropped from the network by
+/// [`Advertisement::stop`].
+pub struct Advertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+impl Advertisement {
+    pub fn start(port: u16, tls_port: Option<u16>) -> Result<Self, String> {
+        let daemon = ServiceDaemon::new().map_err(|e| format!("Failed to start mDNS: {e}"))?;
+
+        let hostname = gethostname::gethostname().to_string_lossy().into_owned();
+        let instance = hostname.split('.').next().unwrap_or("dazzle").to_string();
+
+        let mut properties = vec![
+            ("version", env!("CARGO_PKG_VERSION").to_string()),
+            ("path", "/print".to_string()),
+        ];
+        if let Some(tls_port) = tls_port {
+            properties.push(("tls_port", tls_port.to_string()));
+        }
+
+        let info = ServiceInfo::new(
+            SERVICE_TYPE,
+            &instance,
+            &format!("{instance}.local."),
+            "",
+            port,
+            &properties[..],
+        )
+        .map_err(|e| format!("Invalid mDNS service: {e}"))?
+        .enable_addr_auto();
+
+        let fullname = info.get_fullname().to_string();
+        daemon
+            .register(info)
+            .map_err(|e| format!("Failed to register mDNS service: {e}"))?;
+
+        log::info!("Advertising {fullname} on port {port}");
+        Ok(Self { daemon, fullname })
+    }
+
+    pub fn stop(self) {
+        let _ = self.daemon.unregister(&self.fullname);
+        let _ = self.daemon.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lan(enabled: bool, token: Option<&str>) -> LanConfig {
+        LanConfig {
+            enabled,
+            token: token.map(str::to_string),
+            ..LanConfig::default()
+        }
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn loopback_is_always_allowed() {
+        let config = lan(false, None);
+        assert!(check_address(&config, ip("127.0.0.1")).is_ok());
+        assert!(check_address(&config, ip("::1")).is_ok());
+        assert!(check_address(&config, ip("::ffff:127.0.0.1")).is_ok());
+        assert!(check_token(&config, ip("127.0.0.1"), None).is_ok());
+    }
+
+    #[test]
+    fn remote_clients_rejected_when_lan_disabled() {
+        let config = lan(false, Some("secret"));
+        let err = check_address(&config, ip("192.168.1.50")).unwrap_err();
+        assert_eq!(err.0, StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn remote_clients_must_be_in_allowlist() {
+        let config = lan(true, Some("secret"));
+        assert!(check_address(&config, ip("192.168.1.50")).is_ok());
+        assert!(check_address(&config, ip("::ffff:10.0.0.7")).is_ok());
+        assert!(check_address(&config, ip("8.8.8.8")).is_err());
+    }
+
+    #[test]
+    fn remote_clients_need_the_configured_token() {
+        let config = lan(true, Some("secret"));
+        let peer = ip("192.168.1.50");
+        assert!(check_token(&config, peer, Some("secret")).is_ok());
+        assert_eq!(
+            check_token(&config, peer, Some("wrong")).unwrap_err().0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            check_token(&config, peer, None).unwrap_err().0,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[test]
+    fn lan_without_token_refuses_remote_clients() {
+        let config = lan(true, Some(""));
+        let err = check_token(&config, ip("192.168.1.50"), Some("")).unwrap_err();
+        assert_eq!(err.0, StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn token_is_parsed_from_authorization_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(request_token(&headers), None);
+        headers.insert(header::AUTHORIZATION, "Bearer abc123".parse().unwrap());
+        assert_eq!(request_token(&headers).as_deref(), Some("abc123"));
+        // "phone:abc123"
+        headers.insert(
+            header::AUTHORIZATION,
+            "Basic cGhvbmU6YWJjMTIz".parse().unwrap(),
+        );
+        assert_eq!(request_token(&headers).as_deref(), Some("abc123"));
+        headers.insert(header::AUTHORIZATION, "Basic !!!".parse().unwrap());
+        assert_eq!(request_token(&headers), None);
+    }
+}
@@ -1,8 +1,28 @@
 mod autostart;
+mod browser_print;
 mod commands;
 mod config;
+mod dedupe;
+mod error;
+mod health;
+mod holds;
+mod idempotency;
+mod ipp;
+mod jobs;
+mod lan;
+mod limits;
+mod lpd;
+mod metrics;
+mod peers;
+mod pools;
 mod printing;
+mod qz;
+mod relay;
+mod routing;
+mod schedule;
 mod server;
+mod tls;
+mod webhooks;
 
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
@@ -10,7 +30,15 @@ use tauri::{Emitter, Listener, Manager};
 
 pub struct AppState {
     pub config: RwLock<config::AppConfig>,
-    pub print_jobs: RwLock<Vec<server::PrintJob>>,
+    pub print_jobs: RwLock<Vec<jobs::PrintJob>>,
+    pub pools: pools::PoolState,
+    pub idempotency: idempotency::KeyLocks,
+    pub dedupe: dedupe::RecentJobs,
+    pub webhook_deliveries: RwLock<Vec<webhooks::Delivery>>,
+    pub metrics: metrics::Metrics,
+    pub limits: limits::Limits,
+    pub holds: holds::Holds,
+    pub schedule: schedule::Schedule,
     pub server_handle: tokio::sync::Mutex<Option<server::ServerHandle>>,
     pub app_handle: tauri::AppHandle,
 }
@@ -20,6 +48,7 @@ pub async fn restart_server(state: &Arc<AppState>) -> Result<(), String> {
 
     if let Some(h) = handle_guard.take() {
         h.shutdown().await;
+        state.metrics.server_restarted();
     }
 
     let handle = server::start(state.clone()).await?;
@@ -104,13 +133,103 @@ fn update_tray_status(app: &tauri::AppHandle, running: bool) {
     }
 }
 
-fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
-    use tauri::menu::{Menu, MenuItem};
-    use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+/// Menu id prefix of the per-printer pause items.
+const PAUSE_PRINTER_ID: &str = "pause-printer:";
+
+/// The tray menu, with the pause state of printing and of each of `printers`
+/// and each pool checked.
+fn tray_menu(
+    app: &tauri::AppHandle,
+    mut printers: Vec<String>,
+) -> tauri::Result<tauri::menu::Menu<tauri::Wry>> {
+    use tauri::menu::{CheckMenuItem, IsMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu};
+
+    let state = app.state::<Arc<AppState>>();
+    if let Ok(config) = state.config.read() {
+        printers.extend(config.pools.keys().cloned());
+    }
 
     let show = MenuItem::with_id(app, "show", "Show / Hide", true, None::<&str>)?;
+    let pause_all = CheckMenuItem::with_id(
+        app,
+        "pause-all",
+        "Pause All Printing",
+        true,
+        state.limits.is_paused(),
+        None::<&str>,
+    )?;
+    let pause_items = printers
+        .iter()
+        .map(|name| {
+            CheckMenuItem::with_id(
+                app,
+                format!("{PAUSE_PRINTER_ID}{name}"),
+                name,
+                true,
+                state.holds.is_paused(name),
+                None::<&str>,
+            )
+        })
+        .collect::<tauri::Result<Vec<_>>>()?;
+    let pause_refs: Vec<&dyn IsMenuItem<tauri::Wry>> = pause_items
+        .iter()
+        .map(|item| item as &dyn IsMenuItem<tauri::Wry>)
+        .collect();
+    let pause_printer =
+        Submenu::with_items(app, "Pause Printer", !pause_refs.is_empty(), &pause_refs)?;
     let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-    let menu = Menu::with_items(app, &[&show, &quit])?;
+
+    Menu::with_items(
+        app,
+        &[
+            &show,
+            &PredefinedMenuItem::separator(app)?,
+            &pause_all,
+            &pause_printer,
+            &PredefinedMenuItem::separator(app)?,
+            &quit,
+        ],
+    )
+}
+
+/// Rebuild the tray menu after printing was paused or resumed. Uses the
+/// printers from the last discovery, since this runs on the main thread.
+fn refresh_tray_menu(app: &tauri::AppHandle) {
+    if let Some(tray) = app.tray_by_id("main-tray") {
+        let printers = app.state::<Arc<AppState>>().metrics.discovered();
+        match tray_menu(app, printers) {
+            Ok(menu) => {
+                let _ = tray.set_menu(Some(menu));
+            }
+            Err(e) => log::error!("Failed to rebuild tray menu: {e}"),
+        }
+    }
+}
+
+fn on_tray_menu_event(app: &tauri::AppHandle, id: &str) {
+    let state = app.state::<Arc<AppState>>();
+    match id {
+        "show" => toggle_window(app),
+        "quit" => app.exit(0),
+        "pause-all" => limits::set_paused(&state, !state.limits.is_paused()),
+        _ => {
+            if let Some(printer) = id.strip_prefix(PAUSE_PRINTER_ID) {
+                holds::set_paused(&state, printer, !state.holds.is_paused(printer));
+            }
+        }
+    }
+}
+
+fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
+    use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+
+    let state = app.state::<Arc<AppState>>();
+    let printers = metrics::discover(&state)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| p.name)
+        .collect();
+    let menu = tray_menu(app.handle(), printers)?;
 
     // Start with a red badge — switches to green once the server binds
     let icon = app
@@ -122,11 +241,7 @@ fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
         .icon(icon)
         .tooltip("Dazzle — Starting…")
         .menu(&menu)
-        .on_menu_event(|app, event| match event.id().as_ref() {
-            "show" => toggle_window(app),
-            "quit" => app.exit(0),
-            _ => {}
-        })
+        .on_menu_event(|app, event| on_tray_menu_event(app, event.id().as_ref()))
         .on_tray_icon_event(|tray, event| {
             if let TrayIconEvent::Click {
                 button: MouseButton::Left,
@@ -181,10 +296,20 @@ pub fn run() {
             commands::get_config,
             commands::set_config,
             commands::get_print_jobs,
+            commands::get_webhook_deliveries,
             commands::get_server_running,
             commands::restart_server,
             commands::get_autostart,
             commands::set_autostart,
+            commands::export_tls_ca,
+            commands::get_printer_aliases,
+            commands::set_printer_alias,
+            commands::remove_printer_alias,
+            commands::get_printing_paused,
+            commands::set_printing_paused,
+            commands::set_printer_paused,
+            commands::get_scheduled_jobs,
+            commands::cancel_scheduled_job,
         ])
         .setup(|app| {
             let cfg = config::load();
@@ -205,6 +330,14 @@ pub fn run() {
             let state = Arc::new(AppState {
                 config: RwLock::new(cfg),
                 print_jobs: RwLock::new(Vec::new()),
+                pools: pools::PoolState::default(),
+                idempotency: idempotency::KeyLocks::default(),
+                dedupe: dedupe::RecentJobs::default(),
+                webhook_deliveries: RwLock::new(Vec::new()),
+                metrics: metrics::Metrics::default(),
+                limits: limits::Limits::default(),
+                holds: holds::Holds::default(),
+                schedule: schedule::Schedule::load(),
                 server_handle: tokio::sync::Mutex::new(None),
                 app_handle: app.handle().clone(),
             });
@@ -221,6 +354,16 @@ pub fn run() {
                 }
             });
 
+            // Keep the tray's pause checkmarks in step with the app and API
+            for event in ["printing-paused", "printer-paused"] {
+                let handle = app.handle().clone();
+                app.listen(event, move |_| refresh_tray_menu(&handle));
+            }
+
+            // Print scheduled jobs as they come due, whether or not the
+            // listeners are up
+            tauri::async_runtime::spawn(schedule::run(state.clone()));
+
             // Start the HTTP print server (after listener is registered to avoid race)
             tauri::async_runtime::spawn(async move {
                 if let Err(e) = restart_server(&state).await {
@@ -0,0 +1,274 @@
+//! Protection against runaway clients: per-origin and per-token request rate
+//! limits on the HTTP router, a cap on jobs in progress across every
+//! listener, and the app's "pause all printing" switch.
+//!
+//! Rate limits are token buckets that refill evenly over a minute, so a
+//! client can burst up to its limit and then continues at the steady rate.
+//! Only browsers on this machine are told apart by `Origin`; anything else
+//! could send a new one with each request, so it's limited by address.
+//! Tokens are hashed before they're used as bucket keys.
+
+use crate::error::{Error, ErrorCode};
+use crate::{lan, AppState};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+use tokio::sync::watch;
+
+/// Suggested wait when too many jobs are in progress.
+const BUSY_RETRY_AFTER_SECS: u64 = 5;
+/// Buckets kept before full ones are dropped, so one-off clients don't
+/// accumulate forever.
+const MAX_BUCKETS: usize = 1024;
+
+#[derive(Default)]
+pub struct Limits {
+    buckets: Mutex<HashMap<String, Bucket>>,
+    jobs: AtomicUsize,
+    paused: watch::Sender<bool>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// The limit the bucket was last used with, in requests a minute.
+    per_minute: u32,
+}
+
+impl Bucket {
+    fn capacity(&self) -> f64 {
+        f64::from(self.per_minute)
+    }
+
+    fn per_sec(&self) -> f64 {
+        self.capacity() / 60.0
+    }
+
+    /// The tokens the bucket holds at `now`, refilled since it was updated.
+    fn tokens_at(&self, now: Instant) -> f64 {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * self.per_sec()).min(self.capacity())
+    }
+}
+
+/// Held for as long as a job is in progress.
+pub struct JobSlot<'a> {
+    jobs: &'a AtomicUsize,
+}
+
+impl Drop for JobSlot<'_> {
+    fn drop(&mut self) {
+        self.jobs.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Limits {
+    /// Take one request from `key`'s allowance of `per_minute`, or return how
+    /// long until the next one is available.
+    fn take(&self, key: &str, per_minute: u32, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() >= MAX_BUCKETS {
+            buckets.retain(|_, b| b.tokens_at(now) < b.capacity());
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: f64::from(per_minute),
+            updated: now,
+            per_minute,
+        });
+        bucket.tokens = bucket.tokens_at(now);
+        bucket.updated = now;
+        // The limit may have changed in the settings since the last request
+        bucket.per_minute = per_minute;
+        bucket.tokens = bucket.tokens.min(bucket.capacity());
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / bucket.per_sec(),
+            ))
+        }
+    }
+
+    /// Count a new job in progress, unless `max` (0 for no limit) are
+    /// already in progress or printing is paused.
+    pub fn start_job(&self, max: usize) -> Result<JobSlot<'_>, Error> {
+        if self.is_paused() {
+            return Err(Error::new(
+                ErrorCode::PrintingPaused,
+                "Printing is paused in Dazzle",
+            ));
+        }
+        let previous = self.jobs.fetch_add(1, Ordering::AcqRel);
+        let slot = JobSlot { jobs: &self.jobs };
+        if max > 0 && previous >= max {
+            return Err(Error::new(
+                ErrorCode::TooManyJobs,
+                format!("{max} jobs are already in progress"),
+            )
+            .with_retry_after(BUSY_RETRY_AFTER_SECS));
+        }
+        Ok(slot)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.send_replace(paused);
+    }
+
+    /// Wait until printing isn't paused.
+    pub async fn wait_resumed(&self) {
+        let mut paused = self.paused.subscribe();
+        // The sender lives as long as the app, so this can't fail
+        let _ = paused.wait_for(|paused| !paused).await;
+    }
+}
+
+/// Pause or resume all printing, telling the UI.
+pub fn set_paused(state: &AppState, paused: bool) {
+    state.limits.set_paused(paused);
+    if paused {
+        log::warn!("All printing paused");
+    } else {
+        log::info!("Printing resumed");
+    }
+    state.app_handle.emit("printing-paused", paused).ok();
+}
+
+/// Axum middleware applying the per-origin and per-token rate limits.
+/// Clients on other machines, and any without an `Origin`, are limited by
+/// address instead.
+pub async fn guard(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let limits = match state.config.read() {
+        Ok(config) => config.limits.clone(),
+        Err(e) => return Error::from(e).into_response(),
+    };
+
+    let headers = request.headers();
+    let origin = headers
+        .get(header::ORIGIN)
+        .and_then(|v| v.to_str().ok())
+        .filter(|_| peer.ip().is_loopback())
+        .map(|origin| format!("origin {origin}"))
+        .unwrap_or_else(|| format!("address {}", peer.ip()));
+    let token = lan::request_token(headers)
+        .map(|token| format!("token {:x}", Sha256::digest(token.as_bytes())));
+
+    let now = Instant::now();
+    let mut checks = vec![(origin, limits.per_origin_per_minute)];
+    checks.extend(token.map(|token| (token, limits.per_token_per_minute)));
+
+    for (key, per_minute) in checks {
+        if per_minute == 0 {
+            continue;
+        }
+        if let Err(wait) = state.limits.take(&key, per_minute, now) {
+            // Tokens stay out of the log
+            let client = if key.starts_with("token ") {
+                "token"
+            } else {
+                &key
+            };
+            log::warn!("Rate limited {client} from {peer}");
+            return Error::new(
+                ErrorCode::RateLimited,
+                format!("Rate limit of {per_minute} requests a minute exceeded"),
+            )
+            .with_retry_after(wait.as_secs_f64().ceil() as u64)
+            .into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_allows_a_burst_then_refills() {
+        let limits = Limits::default();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            limits.take("origin a", 3, start).unwrap();
+        }
+        let wait = limits.take("origin a", 3, start).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(20));
+        limits.take("origin b", 3, start).unwrap();
+
+        limits
+            .take("origin a", 3, start + Duration::from_secs(20))
+            .unwrap();
+        limits
+            .take("origin a", 3, start + Duration::from_secs(21))
+            .unwrap_err();
+    }
+
+    #[test]
+    fn full_buckets_are_dropped_by_their_own_limit() {
+        let limits = Limits::default();
+        let start = Instant::now();
+        limits.take("address slow", 1, start).unwrap();
+        for i in 1..MAX_BUCKETS {
+            limits.take(&format!("address {i}"), 600, start).unwrap();
+        }
+
+        // A second later the fast buckets are full again but the slow one,
+        // refilling at one a minute, is still empty and must be kept
+        let later = start + Duration::from_secs(1);
+        limits.take("address new", 600, later).unwrap();
+        let buckets = limits.buckets.lock().unwrap();
+        assert!(buckets.contains_key("address slow"));
+        assert!(!buckets.contains_key("address 1"));
+    }
+
+    #[test]
+    fn jobs_are_capped_until_slots_are_released() {
+        let limits = Limits::default();
+        let first = limits.start_job(2).unwrap();
+        let _second = limits.start_job(2).unwrap();
+
+        let error = limits.start_job(2).err().unwrap();
+        assert_eq!(error.code, ErrorCode::TooManyJobs);
+        assert_eq!(error.retry_after, Some(BUSY_RETRY_AFTER_SECS));
+
+        drop(first);
+        assert!(limits.start_job(2).is_ok());
+        assert!(limits.start_job(0).is_ok());
+    }
+
+    #[test]
+    fn paused_refuses_every_job() {
+        let limits = Limits::default();
+        limits.set_paused(true);
+        assert_eq!(
+            limits.start_job(0).err().unwrap().code,
+            ErrorCode::PrintingPaused
+        );
+        limits.set_paused(false);
+        assert!(limits.start_job(0).is_ok());
+    }
+}
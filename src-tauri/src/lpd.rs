@@ -0,0 +1,338 @@
+//! A minimal RFC 1179 (LPD/LPR) server for clients that can only print over LPR.
+//!
+//! Only "receive a printer job" does real work: every data file it carries is
+//! printed as its own job through [`jobs::run`]. Queue state requests get a
+//! one-line answer, and "print waiting jobs" / "remove jobs" are accepted and
+//! ignored since nothing is ever left waiting.
+
+use crate::config::LpdConfig;
+use crate::{jobs, lan};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+
+/// How long the client may stall on any single read.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// Command lines are short; anything longer is a broken client.
+const MAX_LINE: usize = 1024;
+
+const ACK: u8 = 0;
+const NACK: u8 = 1;
+
+/// Pick the printer for an LPD queue name. With no queues configured every
+/// queue prints to the selected printer; once any are mapped, unknown queues
+/// are refused.
+pub fn resolve_queue(lpd: &LpdConfig, selected: Option<&str>, queue: &str) -> Option<String> {
+    match lpd.queues.get(queue) {
+        Some(printer) => Some(printer.clone()),
+        None if lpd.queues.is_empty() => selected.map(str::to_string),
+        None => None,
+    }
+}
+
+/// Accept LPD connections until cancelled.
+pub async fn serve(listener: TcpListener, state: Arc<crate::AppState>, cancel: CancellationToken) {
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    tokio::spawn(handle_connection(state.clone(), stream, peer));
+                }
+                Err(e) => log::warn!("LPD accept failed: {e}"),
+            },
+        }
+    }
+}
+
+async fn handle_connection(state: Arc<crate::AppState>, stream: TcpStream, peer: SocketAddr) {
+    let (lan, lpd, selected) = match state.config.read() {
+        Ok(config) => (
+            config.lan.clone(),
+            config.lpd.clone(),
+            config.selected_printer.clone(),
+        ),
+        Err(_) => return,
+    };
+
+    if let Err((_, message)) = lan::check_address(&lan, peer.ip()) {
+        log::warn!("Rejected LPD connection from {peer}: {message}");
+        return;
+    }
+
+    let resolve = |queue: &str| resolve_queue(&lpd, selected.as_deref(), queue);
+
+    let received = match session(stream, resolve).await {
+        Ok(Some(received)) => received,
+        Ok(None) => return,
+        Err(e) => {
+            log::warn!("LPD session with {peer} failed: {e}");
+            return;
+        }
+    };
+
+    for data in received.data_files {
+        log::info!(
+            "LPD job from {peer} on queue {}: {} bytes",
+            received.queue,
+            data.len()
+        );
+        if let Err(e) = jobs::run(&state, received.printer.clone(), data).await {
+            log::error!("LPD job from {peer} failed: {e}");
+        }
+    }
+}
+
+/// A "receive a printer job" command that completed.
+#[derive(Debug)]
+struct ReceivedJob {
+    queue: String,
+    printer: String,
+    data_files: Vec<Vec<u8>>,
+}
+
+/// Run one LPD conversation. Returns the received job, if the client sent one.
+async fn session<S, F>(stream: S, resolve: F) -> io::Result<Option<ReceivedJob>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: Fn(&str) -> Option<String>,
+{
+    let mut stream = BufReader::new(stream);
+
+    let Some(line) = read_line(&mut stream).await? else {
+        return Ok(None);
+    };
+    let (&command, operands) = line.split_first().ok_or_else(|| invalid("empty command"))?;
+    let operands = String::from_utf8_lossy(operands);
+    let queue = operands.split_whitespace().next().unwrap_or_default();
+
+    match command {
+        // Print any waiting jobs: nothing ever waits, jobs print on receipt
+        0x01 => Ok(None),
+        // Receive a printer job
+        0x02 => match resolve(queue) {
+            Some(printer) => {
+                stream.write_all(&[ACK]).await?;
+                let data_files = receive_job(&mut stream).await?;
+                Ok(Some(ReceivedJob {
+                    queue: queue.to_string(),
+                    printer,
+                    data_files,
+                }))
+            }
+            None => {
+                log::warn!("LPD job for unknown queue {queue:?} refused");
+                stream.write_all(&[NACK]).await?;
+                Ok(None)
+            }
+        },
+        // Send queue state (short / long)
+        0x03 | 0x04 => {
+            let state = match resolve(queue) {
+                Some(printer) => format!("{queue}: ready, printing to {printer}\nno entries\n"),
+                None => format!("{queue}: unknown queue\n"),
+            };
+            stream.write_all(state.as_bytes()).await?;
+            stream.flush().await?;
+            Ok(None)
+        }
+        // Remove jobs: jobs are printed as soon as they arrive
+        0x05 => Ok(None),
+        other => Err(invalid(&format!("unknown command {other:#04x}"))),
+    }
+}
+
+/// Handle the subcommands of "receive a printer job" until the client hangs
+/// up, collecting every data file.
+async fn receive_job<S>(stream: &mut BufReader<S>) -> io::Result<Vec<Vec<u8>>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut data_files = Vec::new();
+
+    while let Some(line) = read_line(stream).await? {
+        let (&subcommand, operands) = line
+            .split_first()
+            .ok_or_else(|| invalid("empty subcommand"))?;
+
+        match subcommand {
+            // Abort job
+            0x01 => return Ok(Vec::new()),
+            // Receive control file / data file: "<count> <name>"
+            0x02 | 0x03 => {
+                let operands = String::from_utf8_lossy(operands);
+                let count: usize = operands
+                    .split_whitespace()
+                    .next()
+                    .and_then(|c| c.parse().ok())
+                    .ok_or_else(|| invalid("bad file length"))?;
+                if count > jobs::MAX_JOB_SIZE {
+                    stream.write_all(&[NACK]).await?;
+                    return Err(invalid("file too large"));
+                }
+                stream.write_all(&[ACK]).await?;
+
+                // The file is followed by a single zero octet
+                let mut file = vec![0u8; count + 1];
+                timed(stream.read_exact(&mut file)).await?;
+                file.pop();
+                stream.write_all(&[ACK]).await?;
+
+                // The control file only names the data files; we print all of them
+                if subcommand == 0x03 {
+                    data_files.push(file);
+                }
+            }
+            other => {
+                stream.write_all(&[NACK]).await?;
+                return Err(invalid(&format!("unknown subcommand {other:#04x}")));
+            }
+        }
+    }
+
+    Ok(data_files)
+}
+
+/// Read one LF-terminated command line, without the LF. `None` on clean EOF.
+async fn read_line<S>(stream: &mut BufReader<S>) -> io::Result<Option<Vec<u8>>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut line = Vec::new();
+    let n = timed(
+        (&mut *stream)
+            .take(MAX_LINE as u64)
+            .read_until(b'\n', &mut line),
+    )
+    .await?;
+    if n == 0 {
+        return Ok(None);
+    }
+    if line.pop() != Some(b'\n') {
+        return Err(invalid("command line too long or truncated"));
+    }
+    Ok(Some(line))
+}
+
+async fn timed<T>(fut: impl std::future::Future<Output = io::Result<T>>) -> io::Result<T> {
+    tokio::time::timeout(READ_TIMEOUT, fut)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "client stopped sending"))?
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn resolver(queue: &str) -> Option<String> {
+        (queue == "zebra").then(|| "ZebraRaw".to_string())
+    }
+
+    /// Run `session` against a scripted client, returning the job and
+    /// everything the server wrote back.
+    async fn run_session(client_bytes: Vec<u8>) -> (io::Result<Option<ReceivedJob>>, Vec<u8>) {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let (mut client_read, mut client_write) = tokio::io::split(client);
+
+        let writer = tokio::spawn(async move {
+            client_write.write_all(&client_bytes).await.unwrap();
+            client_write.shutdown().await.unwrap();
+        });
+        let reader = tokio::spawn(async move {
+            let mut replies = Vec::new();
+            client_read.read_to_end(&mut replies).await.unwrap();
+            replies
+        });
+
+        let result = session(server, resolver).await;
+        writer.await.unwrap();
+        (result, reader.await.unwrap())
+    }
+
+    fn receive_job_bytes(queue: &str, data: &[u8]) -> Vec<u8> {
+        let control = b"Hdazzle\nPtester\nldfA001host\n";
+        let mut bytes = format!("\x02{queue}\n").into_bytes();
+        bytes.extend(format!("\x02{} cfA001host\n", control.len()).as_bytes());
+        bytes.extend(control);
+        bytes.push(0);
+        bytes.extend(format!("\x03{} dfA001host\n", data.len()).as_bytes());
+        bytes.extend(data);
+        bytes.push(0);
+        bytes
+    }
+
+    #[tokio::test]
+    async fn receives_data_file_for_known_queue() {
+        let (result, replies) = run_session(receive_job_bytes("zebra", b"^XA^FDHi^XZ")).await;
+
+        let job = result.unwrap().unwrap();
+        assert_eq!(job.queue, "zebra");
+        assert_eq!(job.printer, "ZebraRaw");
+        assert_eq!(job.data_files, vec![b"^XA^FDHi^XZ".to_vec()]);
+        // Command, control header, control file, data header, data file
+        assert_eq!(replies, vec![ACK; 5]);
+    }
+
+    #[tokio::test]
+    async fn refuses_unknown_queue() {
+        let (result, replies) = run_session(receive_job_bytes("nope", b"^XA^XZ")).await;
+
+        assert!(result.unwrap().is_none());
+        assert_eq!(replies, vec![NACK]);
+    }
+
+    #[tokio::test]
+    async fn abort_discards_received_files() {
+        let mut bytes = receive_job_bytes("zebra", b"^XA^XZ");
+        bytes.extend(b"\x01\n");
+
+        let (result, _) = run_session(bytes).await;
+
+        assert!(result.unwrap().unwrap().data_files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reports_queue_state() {
+        let (result, replies) = run_session(b"\x03zebra\n".to_vec()).await;
+
+        assert!(result.unwrap().is_none());
+        let text = String::from_utf8(replies).unwrap();
+        assert!(text.starts_with("zebra: ready"));
+    }
+
+    #[tokio::test]
+    async fn truncated_data_file_is_an_error() {
+        let mut bytes = b"\x02zebra\n\x0310 dfA001host\n".to_vec();
+        bytes.extend(b"^XA");
+
+        let (result, _) = run_session(bytes).await;
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn queues_resolve_through_the_map() {
+        let mut lpd = LpdConfig::default();
+        assert_eq!(
+            resolve_queue(&lpd, Some("ZebraRaw"), "anything").as_deref(),
+            Some("ZebraRaw")
+        );
+        assert_eq!(resolve_queue(&lpd, None, "anything"), None);
+
+        lpd.queues = BTreeMap::from([("labels".to_string(), "Zebra_ZD420".to_string())]);
+        assert_eq!(
+            resolve_queue(&lpd, Some("ZebraRaw"), "labels").as_deref(),
+            Some("Zebra_ZD420")
+        );
+        assert_eq!(resolve_queue(&lpd, Some("ZebraRaw"), "anything"), None);
+    }
+}
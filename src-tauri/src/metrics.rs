@@ -0,0 +1,344 @@
+//! Counters for `GET /metrics`, rendered in the Prometheus text format.
+//!
+//! Everything is kept in memory for the life of the app, so counters start
+//! from zero on every launch like any other Prometheus target. Printers are
+//! labelled by name only if they're configured or were found by discovery;
+//! any other name a request makes up is counted as `other`.
+
+use crate::config::AppConfig;
+use crate::error::Error;
+use crate::jobs::JobStatus;
+use crate::{printing, AppState};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The `printer` label for names that aren't configured or discovered.
+const OTHER_PRINTER: &str = "other";
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+const BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+#[derive(Default)]
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Finished jobs by printer and status.
+    jobs: BTreeMap<(String, String), u64>,
+    bytes_sent: BTreeMap<String, u64>,
+    send_duration: BTreeMap<String, Histogram>,
+    /// Sends in progress, per printer.
+    in_flight: BTreeMap<String, u64>,
+    /// Whether the last send to each printer worked.
+    printer_up: BTreeMap<String, bool>,
+    discovery_duration: Histogram,
+    server_restarts: u64,
+    /// Printers found by the last discovery, in the order they were listed.
+    discovered: Vec<String>,
+}
+
+#[derive(Default, Clone)]
+struct Histogram {
+    /// Observations per bucket in [`BUCKETS`], not cumulative.
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: Duration) {
+        let secs = value.as_secs_f64();
+        if let Some(i) = BUCKETS.iter().position(|bound| secs <= *bound) {
+            self.buckets[i] += 1;
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let sep = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(self.buckets) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{{labels}{sep}le=\"{bound}\"}} {cumulative}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{{labels}{sep}le=\"+Inf\"}} {}",
+            self.count
+        );
+        let braced = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        let _ = writeln!(out, "{name}_sum{braced} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{braced} {}", self.count);
+    }
+}
+
+impl Metrics {
+    fn inner(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A send of a job to `printer` started.
+    pub fn send_started(&self, printer: &str) {
+        *self
+            .inner()
+            .in_flight
+            .entry(printer.to_string())
+            .or_insert(0) += 1;
+    }
+
+    /// A send to `printer` started by [`Metrics::send_started`] finished.
+    pub fn send_finished(&self, printer: &str, bytes: usize, elapsed: Duration, ok: bool) {
+        let mut inner = self.inner();
+        if let Some(count) = inner.in_flight.get_mut(printer) {
+            *count = count.saturating_sub(1);
+        }
+        if ok {
+            *inner.bytes_sent.entry(printer.to_string()).or_insert(0) += bytes as u64;
+        }
+        inner
+            .send_duration
+            .entry(printer.to_string())
+            .or_default()
+            .observe(elapsed);
+        inner.printer_up.insert(printer.to_string(), ok);
+    }
+
+    /// The `printer` label for `printer`: its name if it's configured or
+    /// was discovered, otherwise `other`.
+    fn printer_label<'a>(&self, config: &AppConfig, printer: &'a str) -> &'a str {
+        if config.is_configured_printer(printer)
+            || self.inner().discovered.iter().any(|p| p == printer)
+        {
+            printer
+        } else {
+            OTHER_PRINTER
+        }
+    }
+
+    /// Names of the printers found by the last discovery, for lists that
+    /// can't wait for a new one.
+    pub fn discovered(&self) -> Vec<String> {
+        self.inner().discovered.clone()
+    }
+
+    pub fn job_finished(&self, printer: &str, status: &JobStatus) {
+        let status = match status {
+            JobStatus::Scheduled => "scheduled",
+            JobStatus::Held => "held",
+            JobStatus::Printing => "printing",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+        };
+        *self
+            .inner()
+            .jobs
+            .entry((printer.to_string(), status.to_string()))
+            .or_insert(0) += 1;
+    }
+
+    /// Sends in progress across all printers.
+    pub fn queue_depth(&self) -> u64 {
+        self.inner().in_flight.values().sum()
+    }
+
+    pub fn server_restarted(&self) {
+        self.inner().server_restarts += 1;
+    }
+
+    /// Everything recorded so far, in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let inner = self.inner();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "dazzle_jobs_total",
+            "counter",
+            "Finished print jobs.",
+        );
+        for ((printer, status), count) in &inner.jobs {
+            let _ = writeln!(
+                out,
+                "dazzle_jobs_total{{printer=\"{}\",status=\"{}\"}} {count}",
+                escape(printer),
+                escape(status)
+            );
+        }
+
+        header(
+            &mut out,
+            "dazzle_bytes_sent_total",
+            "counter",
+            "Bytes sent to printers.",
+        );
+        for (printer, bytes) in &inner.bytes_sent {
+            let _ = writeln!(
+                out,
+                "dazzle_bytes_sent_total{{printer=\"{}\"}} {bytes}",
+                escape(printer)
+            );
+        }
+
+        header(
+            &mut out,
+            "dazzle_send_duration_seconds",
+            "histogram",
+            "Time taken to hand a job to the printer or spooler.",
+        );
+        for (printer, histogram) in &inner.send_duration {
+            let labels = format!("printer=\"{}\"", escape(printer));
+            histogram.render(&mut out, "dazzle_send_duration_seconds", &labels);
+        }
+
+        header(
+            &mut out,
+            "dazzle_queue_depth",
+            "gauge",
+            "Jobs currently being sent, per printer.",
+        );
+        for (printer, count) in &inner.in_flight {
+            let _ = writeln!(
+                out,
+                "dazzle_queue_depth{{printer=\"{}\"}} {count}",
+                escape(printer)
+            );
+        }
+
+        header(
+            &mut out,
+            "dazzle_printer_up",
+            "gauge",
+            "Whether the last job sent to the printer succeeded.",
+        );
+        for (printer, up) in &inner.printer_up {
+            let _ = writeln!(
+                out,
+                "dazzle_printer_up{{printer=\"{}\"}} {}",
+                escape(printer),
+                u8::from(*up)
+            );
+        }
+
+        header(
+            &mut out,
+            "dazzle_discovery_duration_seconds",
+            "histogram",
+            "Time taken to list the system's printers.",
+        );
+        inner
+            .discovery_duration
+            .render(&mut out, "dazzle_discovery_duration_seconds", "");
+
+        header(
+            &mut out,
+            "dazzle_server_restarts_total",
+            "counter",
+            "Times the listeners were restarted.",
+        );
+        let _ = writeln!(
+            out,
+            "dazzle_server_restarts_total {}",
+            inner.server_restarts
+        );
+
+        out
+    }
+}
+
+/// [`printing::discover`], timed for `dazzle_discovery_duration_seconds`.
+pub fn discover(state: &AppState) -> Result<Vec<printing::Printer>, Error> {
+    let started = Instant::now();
+    let result = printing::discover();
+    let mut inner = state.metrics.inner();
+    inner.discovery_duration.observe(started.elapsed());
+    if let Ok(printers) = &result {
+        inner.discovered = printers.iter().map(|p| p.name.clone()).collect();
+    }
+    result
+}
+
+/// The `printer` label to record a job or send to `printer` under.
+pub fn printer_label<'a>(state: &AppState, printer: &'a str) -> &'a str {
+    match state.config.read() {
+        Ok(config) => state.metrics.printer_label(&config, printer),
+        Err(_) => OTHER_PRINTER,
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Escape a label value: backslashes, quotes and newlines.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_counters_and_histograms() {
+        let metrics = Metrics::default();
+        metrics.send_started("Zebra \"A\"");
+        metrics.send_finished("Zebra \"A\"", 120, Duration::from_millis(30), true);
+        metrics.job_finished("Zebra \"A\"", &JobStatus::Completed);
+        metrics.send_started("ZebraB");
+        metrics.server_restarted();
+
+        let text = metrics.render();
+        assert!(
+            text.contains("dazzle_jobs_total{printer=\"Zebra \\\"A\\\"\",status=\"completed\"} 1")
+        );
+        assert!(text.contains("dazzle_bytes_sent_total{printer=\"Zebra \\\"A\\\"\"} 120"));
+        assert!(text.contains(
+            "dazzle_send_duration_seconds_bucket{printer=\"Zebra \\\"A\\\"\",le=\"0.025\"} 0"
+        ));
+        assert!(text.contains(
+            "dazzle_send_duration_seconds_bucket{printer=\"Zebra \\\"A\\\"\",le=\"0.05\"} 1"
+        ));
+        assert!(text.contains(
+            "dazzle_send_duration_seconds_bucket{printer=\"Zebra \\\"A\\\"\",le=\"+Inf\"} 1"
+        ));
+        assert!(text.contains("dazzle_queue_depth{printer=\"ZebraB\"} 1"));
+        assert!(text.contains("dazzle_printer_up{printer=\"Zebra \\\"A\\\"\"} 1"));
+        assert!(text.contains("dazzle_discovery_duration_seconds_count 0"));
+        assert!(text.contains("dazzle_server_restarts_total 1"));
+    }
+
+    #[test]
+    fn unknown_printers_are_labelled_other() {
+        let metrics = Metrics::default();
+        metrics.inner().discovered.push("ZebraUSB".to_string());
+        let mut config = AppConfig::default();
+        config
+            .aliases
+            .insert("shipping".to_string(), "tcp://10.0.0.5:9100".to_string());
+
+        assert_eq!(metrics.printer_label(&config, "ZebraUSB"), "ZebraUSB");
+        assert_eq!(
+            metrics.printer_label(&config, "tcp://10.0.0.5:9100"),
+            "tcp://10.0.0.5:9100"
+        );
+        assert_eq!(metrics.printer_label(&config, "made-up-1234"), "other");
+    }
+}
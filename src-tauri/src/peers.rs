@@ -0,0 +1,304 @@
+//! Forwarding jobs to printers attached to another Dazzle station.
+//!
+//! A printer target of `dazzle://host:29100/PrinterName` (usually the target
+//! of an alias or a pool member) sends the job to that station's
+//! `POST /print`. The remote job id and outcome are copied onto the local job,
+//! so the history shows what happened on the other end.
+//!
+//! Forwarded requests are marked with [`FORWARDED_HEADER`] and a station
+//! never forwards such a job again, so a misconfigured mesh can't loop.
+
+use crate::error::{Error, ErrorCode};
+use axum::http::HeaderMap;
+use reqwest::Url;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+pub const SCHEME: &str = "dazzle://";
+
+/// Port of a peer whose address doesn't name one.
+const DEFAULT_PORT: u16 = 29100;
+/// The peer answers once the job has printed, so allow for a slow spooler.
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Marks a job sent by another station. Any client can send it, which is
+/// fine: it only stops the job being forwarded again.
+pub const FORWARDED_HEADER: &str = "X-Dazzle-Forwarded";
+/// Id of the job `POST /print` created, on success and failure alike.
+pub const JOB_ID_HEADER: &str = "X-Dazzle-Job-Id";
+
+/// Whether `printer` is on another Dazzle station.
+pub fn is_peer(printer: &str) -> bool {
+    printer.starts_with(SCHEME)
+}
+
+/// Whether a request came from another station forwarding a job.
+pub fn is_forwarded(headers: &HeaderMap) -> bool {
+    headers.contains_key(FORWARDED_HEADER)
+}
+
+/// A parsed `dazzle://` target.
+#[derive(Debug, PartialEq)]
+struct Target {
+    /// `host:port`, also the key for the station's token.
+    address: String,
+    printer: String,
+}
+
+fn parse(target: &str) -> Result<Target, Error> {
+    let invalid = || {
+        Error::new(
+            ErrorCode::InvalidRequest,
+            format!("Invalid peer printer {target}, expected dazzle://host:port/PrinterName"),
+        )
+    };
+
+    let url = Url::parse(target).map_err(|_| invalid())?;
+    let host = url
+        .host_str()
+        .filter(|h| !h.is_empty())
+        .ok_or_else(invalid)?;
+    let port = url.port().unwrap_or(DEFAULT_PORT);
+
+    let printer = url
+        .path_segments()
+        .and_then(|mut segments| segments.next())
+        .filter(|name| !name.is_empty())
+        .ok_or_else(invalid)?;
+    let printer = percent_decode(printer).ok_or_else(invalid)?;
+
+    Ok(Target {
+        address: format!("{host}:{port}"),
+        printer,
+    })
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// What the peer made of a forwarded job.
+#[derive(Debug)]
+pub struct Forwarded {
+    /// The job's id on the peer, when the peer got as far as creating one.
+    pub job_id: Option<String>,
+    pub result: Result<(), Error>,
+}
+
+impl Forwarded {
+    fn failed(error: Error) -> Self {
+        Self {
+            job_id: None,
+            result: Err(error),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PrintResponse {
+    job_id: String,
+}
+
+/// Send `data` to the printer `target` names on another station, with the
+/// token configured for that station in `tokens`.
+pub async fn forward(target: &str, data: &[u8], tokens: &BTreeMap<String, String>) -> Forwarded {
+    let target = match parse(target) {
+        Ok(target) => target,
+        Err(e) => return Forwarded::failed(e),
+    };
+
+    let mut url = match Url::parse(&format!("http://{}/print", target.address)) {
+        Ok(url) => url,
+        Err(e) => {
+            return Forwarded::failed(Error::new(
+                ErrorCode::InvalidRequest,
+                format!("Invalid peer address: {e}"),
+            ));
+        }
+    };
+    url.query_pairs_mut()
+        .append_pair("printer", &target.printer);
+
+    let client = match reqwest::Client::builder().timeout(FORWARD_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            return Forwarded::failed(Error::internal(format!(
+                "Failed to create HTTP client: {e}"
+            )));
+        }
+    };
+    let mut request = client
+        .post(url)
+        .header(FORWARDED_HEADER, "1")
+        .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+        .body(data.to_vec());
+    if let Some(token) = tokens.get(&target.address).filter(|t| !t.is_empty()) {
+        request = request.bearer_auth(token);
+    }
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            return Forwarded::failed(Error::new(
+                ErrorCode::PeerError,
+                format!("Peer {} unreachable: {e}", target.address),
+            ));
+        }
+    };
+
+    let status = response.status();
+    let job_id = response
+        .headers()
+        .get(JOB_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let body = response.bytes().await.unwrap_or_default();
+
+    if status.is_success() {
+        let job_id = job_id.or_else(|| {
+            serde_json::from_slice::<PrintResponse>(&body)
+                .ok()
+                .map(|r| r.job_id)
+        });
+        Forwarded {
+            job_id,
+            result: Ok(()),
+        }
+    } else {
+        Forwarded {
+            job_id,
+            result: Err(remote_error(&target.address, status, &body)),
+        }
+    }
+}
+
+/// The error a peer answered with. The printer's own problems keep their code;
+/// anything else, like a missing token, is the peer's problem.
+fn remote_error(address: &str, status: reqwest::StatusCode, body: &[u8]) -> Error {
+    let (code, message) = match serde_json::from_slice::<Error>(body) {
+        Ok(remote) => (remote.code, remote.to_string()),
+        Err(_) => (
+            ErrorCode::PeerError,
+            String::from_utf8_lossy(body).trim().to_string(),
+        ),
+    };
+    let code = match code {
+        ErrorCode::PrinterNotFound | ErrorCode::PrinterOffline | ErrorCode::SpoolerError => code,
+        _ => ErrorCode::PeerError,
+    };
+    Error::new(code, format!("Peer {address} answered {status}: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Bytes,
+        extract::Query,
+        http::StatusCode,
+        response::{IntoResponse, Response},
+        routing::post,
+        Router,
+    };
+
+    #[test]
+    fn parses_peer_targets() {
+        assert_eq!(
+            parse("dazzle://192.168.1.20:29100/ZebraRaw").unwrap(),
+            Target {
+                address: "192.168.1.20:29100".to_string(),
+                printer: "ZebraRaw".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("dazzle://frontdesk.local/Zebra%20GK420d").unwrap(),
+            Target {
+                address: "frontdesk.local:29100".to_string(),
+                printer: "Zebra GK420d".to_string(),
+            }
+        );
+        assert!(parse("dazzle://frontdesk.local").is_err());
+        assert!(parse("dazzle:///ZebraRaw").is_err());
+    }
+
+    /// A peer that prints on `ZebraRaw` and fails everything else.
+    async fn print_handler(
+        Query(query): Query<Vec<(String, String)>>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Response {
+        let authorized = headers
+            .get("authorization")
+            .is_some_and(|v| v == "Bearer s3cret");
+        if !authorized || !is_forwarded(&headers) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+
+        let printer = query.iter().find(|(k, _)| k == "printer").map(|(_, v)| v);
+        if printer.is_some_and(|p| p == "ZebraRaw") && !body.is_empty() {
+            axum::Json(serde_json::json!({ "job_id": "remote-1" })).into_response()
+        } else {
+            (
+                [(JOB_ID_HEADER, "remote-2")],
+                Error::new(ErrorCode::PrinterOffline, "lp failed")
+                    .with_details("printer is jammed")
+                    .with_job_id("remote-2"),
+            )
+                .into_response()
+        }
+    }
+
+    async fn peer() -> String {
+        let app = Router::new().route("/print", post(print_handler));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        addr.to_string()
+    }
+
+    #[tokio::test]
+    async fn forwards_and_mirrors_the_remote_job() {
+        let address = peer().await;
+        let tokens = BTreeMap::from([(address.clone(), "s3cret".to_string())]);
+
+        let printed = forward(&format!("dazzle://{address}/ZebraRaw"), b"^XA^XZ", &tokens).await;
+        assert_eq!(printed.job_id.as_deref(), Some("remote-1"));
+        assert!(printed.result.is_ok());
+
+        let failed = forward(&format!("dazzle://{address}/Jammed"), b"^XA^XZ", &tokens).await;
+        assert_eq!(failed.job_id.as_deref(), Some("remote-2"));
+        let error = failed.result.unwrap_err();
+        assert_eq!(error.code, ErrorCode::PrinterOffline);
+        assert!(error.message.contains("503"), "{error}");
+        assert!(error.message.ends_with("printer is jammed"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn missing_token_is_reported() {
+        let address = peer().await;
+        let forwarded = forward(
+            &format!("dazzle://{address}/ZebraRaw"),
+            b"^XA^XZ",
+            &BTreeMap::new(),
+        )
+        .await;
+        assert_eq!(forwarded.job_id, None);
+        let error = forwarded.result.unwrap_err();
+        assert_eq!(error.code, ErrorCode::PeerError);
+        assert!(error.message.contains("401"), "{error}");
+    }
+}
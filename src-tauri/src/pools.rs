@@ -0,0 +1,144 @@
+//! Printer pools: one name that dispatches each job to one of several
+//! printers, so a jammed printer doesn't stop the line.
+//!
+//! [`PoolState`] picks the order members are tried in. Members that failed
+//! recently are put last until their cooldown runs out, and the job pipeline
+//! moves on to the next member whenever a send fails.
+
+use crate::config::{PoolConfig, PoolStrategy};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a member that failed is skipped in favour of healthy ones.
+const FAILURE_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Runtime bookkeeping for pool dispatch, shared by every job.
+#[derive(Default)]
+pub struct PoolState {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Round-robin cursor per pool.
+    next: HashMap<String, usize>,
+    /// Jobs currently being sent, per printer.
+    in_flight: HashMap<String, usize>,
+    /// When each printer last failed.
+    failed_at: HashMap<String, Instant>,
+}
+
+impl PoolState {
+    /// The members of `pool` in the order they should be tried. Members must
+    /// already be resolved from aliases, since [`begin`](Self::begin) and
+    /// [`finish`](Self::finish) see the printer a job was sent to.
+    pub fn candidates(&self, name: &str, pool: &PoolConfig) -> Vec<String> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let mut members = pool.members.clone();
+
+        match pool.strategy {
+            PoolStrategy::Failover => {}
+            PoolStrategy::RoundRobin => {
+                let len = members.len();
+                if len > 0 {
+                    let next = inner.next.entry(name.to_string()).or_insert(0);
+                    members.rotate_left(*next % len);
+                    *next = next.wrapping_add(1);
+                }
+            }
+            PoolStrategy::LeastQueued => {
+                // Stable, so ties keep the configured order
+                members.sort_by_key(|m| inner.in_flight.get(m).copied().unwrap_or(0));
+            }
+        }
+
+        // Unhealthy members stay in the list as a last resort
+        let now = Instant::now();
+        members.sort_by_key(|m| {
+            inner
+                .failed_at
+                .get(m)
+                .is_some_and(|at| now.duration_since(*at) < FAILURE_COOLDOWN)
+        });
+
+        members
+    }
+
+    /// Mark a send to `printer` as started.
+    pub fn begin(&self, printer: &str) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        *inner.in_flight.entry(printer.to_string()).or_insert(0) += 1;
+    }
+
+    /// Mark a send to `printer` as finished, recording whether it worked.
+    pub fn finish(&self, printer: &str, ok: bool) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = inner.in_flight.get_mut(printer) {
+            *count = count.saturating_sub(1);
+        }
+        if ok {
+            inner.failed_at.remove(printer);
+        } else {
+            inner.failed_at.insert(printer.to_string(), Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(strategy: PoolStrategy) -> PoolConfig {
+        PoolConfig {
+            members: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            strategy,
+        }
+    }
+
+    #[test]
+    fn failover_keeps_configured_order() {
+        let state = PoolState::default();
+        let pool = pool(PoolStrategy::Failover);
+        assert_eq!(state.candidates("p", &pool), ["a", "b", "c"]);
+        assert_eq!(state.candidates("p", &pool), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn round_robin_rotates_the_first_member() {
+        let state = PoolState::default();
+        let pool = pool(PoolStrategy::RoundRobin);
+        assert_eq!(state.candidates("p", &pool), ["a", "b", "c"]);
+        assert_eq!(state.candidates("p", &pool), ["b", "c", "a"]);
+        assert_eq!(state.candidates("p", &pool), ["c", "a", "b"]);
+        assert_eq!(state.candidates("p", &pool), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn least_queued_prefers_idle_members() {
+        let state = PoolState::default();
+        let pool = pool(PoolStrategy::LeastQueued);
+        state.begin("a");
+        state.begin("a");
+        state.begin("b");
+        assert_eq!(state.candidates("p", &pool), ["c", "b", "a"]);
+
+        state.finish("a", true);
+        state.finish("a", true);
+        assert_eq!(state.candidates("p", &pool), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn failed_members_are_tried_last_until_they_recover() {
+        let state = PoolState::default();
+        let pool = pool(PoolStrategy::Failover);
+
+        state.begin("a");
+        state.finish("a", false);
+        assert_eq!(state.candidates("p", &pool), ["b", "c", "a"]);
+
+        state.begin("a");
+        state.finish("a", true);
+        assert_eq!(state.candidates("p", &pool), ["a", "b", "c"]);
+    }
+}
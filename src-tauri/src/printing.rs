@@ -1,24 +1,36 @@
+use crate::error::{Error, ErrorCode};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Printer {
     pub name: String,
     pub is_default: bool,
+    /// For aliases, the printer (or `tcp://` or `dazzle://` address) the
+    /// alias prints on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_for: Option<String>,
+    /// Whether jobs for the printer are being held, see [`crate::holds`].
+    #[serde(default)]
+    pub paused: bool,
 }
 
 // ─── macOS / Linux ──────────────────────────────────────────────────────────
 
 #[cfg(not(target_os = "windows"))]
 mod platform {
-    use super::Printer;
+    use super::{Printer, PrinterState};
+    use crate::error::{Error, ErrorCode};
     use std::process::Command;
 
     /// Discover printers using `lpstat` (macOS/Linux).
-    pub fn discover() -> Result<Vec<Printer>, String> {
-        let output = Command::new("lpstat")
-            .arg("-p")
-            .output()
-            .map_err(|e| format!("Failed to run lpstat: {e}"))?;
+    pub fn discover() -> Result<Vec<Printer>, Error> {
+        let output = Command::new("lpstat").arg("-p").output().map_err(|e| {
+            Error::new(
+                ErrorCode::SpoolerError,
+                format!("Failed to run lpstat: {e}"),
+            )
+        })?;
 
         // No printers configured returns non-zero — that's fine, just empty list
         if !output.status.success() {
@@ -46,7 +58,12 @@ mod platform {
                     .next()?
                     .to_string();
                 let is_default = default_printer.as_deref() == Some(&name);
-                Some(Printer { name, is_default })
+                Some(Printer {
+                    name,
+                    is_default,
+                    alias_for: None,
+                    paused: false,
+                })
             })
             .collect();
 
@@ -54,44 +71,98 @@ mod platform {
     }
 
     /// Send raw bytes to a printer using `lp -o raw` (macOS/Linux).
-    pub fn send_raw(printer: &str, data: &[u8]) -> Result<(), String> {
+    pub fn send_raw(printer: &str, data: &[u8]) -> Result<(), Error> {
         use std::io::Write;
 
+        let spooler = |message: String| Error::new(ErrorCode::SpoolerError, message);
         let mut child = Command::new("lp")
             .args(["-d", printer, "-o", "raw"])
             .stdin(std::process::Stdio::piped())
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
             .spawn()
-            .map_err(|e| format!("Failed to spawn lp: {e}"))?;
+            .map_err(|e| spooler(format!("Failed to spawn lp: {e}")))?;
 
         child
             .stdin
             .take()
             .unwrap()
             .write_all(data)
-            .map_err(|e| format!("Failed to write to lp: {e}"))?;
+            .map_err(|e| spooler(format!("Failed to write to lp: {e}")))?;
 
         let output = child
             .wait_with_output()
-            .map_err(|e| format!("Failed to wait for lp: {e}"))?;
+            .map_err(|e| spooler(format!("Failed to wait for lp: {e}")))?;
 
         if output.status.success() {
             Ok(())
         } else {
-            Err(format!(
-                "lp failed: {}",
-                String::from_utf8_lossy(&output.stderr)
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            Err(Error::new(lp_error_code(&stderr), "lp failed").with_details(stderr))
+        }
+    }
+
+    /// Tell a missing or stopped printer apart from other `lp` failures.
+    pub(super) fn lp_error_code(stderr: &str) -> ErrorCode {
+        let stderr = stderr.to_lowercase();
+        if stderr.contains("does not exist") || stderr.contains("unknown destination") {
+            ErrorCode::PrinterNotFound
+        } else if stderr.contains("not accepting jobs") {
+            ErrorCode::PrinterOffline
+        } else {
+            ErrorCode::SpoolerError
+        }
+    }
+
+    /// Whether the CUPS scheduler is running, per `lpstat -r`.
+    pub fn check_spooler() -> Result<(), Error> {
+        let output = Command::new("lpstat").arg("-r").output().map_err(|e| {
+            Error::new(
+                ErrorCode::SpoolerError,
+                format!("Failed to run lpstat: {e}"),
+            )
+        })?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if output.status.success() && !stdout.contains("not running") {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorCode::SpoolerError,
+                "The CUPS scheduler is not running",
             ))
         }
     }
+
+    pub fn printer_state(printer: &str) -> Option<PrinterState> {
+        let output = Command::new("lpstat").args(["-p", printer]).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_printer_state(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// The state in `lpstat -p` output: "printer NAME is idle.",
+    /// "printer NAME now printing NAME-12." or "printer NAME disabled since ...".
+    pub(super) fn parse_printer_state(stdout: &str) -> Option<PrinterState> {
+        let line = stdout.lines().find(|l| l.starts_with("printer "))?;
+        if line.contains(" disabled") {
+            Some(PrinterState::Stopped)
+        } else if line.contains(" now printing") {
+            Some(PrinterState::Printing)
+        } else if line.contains(" is idle") {
+            Some(PrinterState::Idle)
+        } else {
+            None
+        }
+    }
 }
 
 // ─── Windows ────────────────────────────────────────────────────────────────
 
 #[cfg(target_os = "windows")]
 mod platform {
-    use super::Printer;
+    use super::{Printer, PrinterState};
+    use crate::error::{Error, ErrorCode};
     use std::ffi::c_void;
     use windows::core::{HSTRING, PCWSTR, PWSTR};
     use windows::Win32::Graphics::Printing::{
@@ -101,7 +172,7 @@ mod platform {
     };
 
     /// Discover printers via the Win32 `EnumPrintersW` API.
-    pub fn discover() -> Result<Vec<Printer>, String> {
+    pub fn discover() -> Result<Vec<Printer>, Error> {
         let default_name = get_default_printer_name();
 
         // First call: determine required buffer size.
@@ -135,7 +206,7 @@ mod platform {
                 &mut bytes_needed,
                 &mut count,
             )
-            .map_err(|e| format!("EnumPrintersW failed: {e}"))?;
+            .map_err(|e| spooler(format!("EnumPrintersW failed: {e}")))?;
         }
 
         let infos = unsafe {
@@ -147,7 +218,12 @@ mod platform {
             .filter_map(|info| {
                 let name = unsafe { info.pPrinterName.to_string().ok()? };
                 let is_default = default_name.as_deref() == Some(name.as_str());
-                Some(Printer { name, is_default })
+                Some(Printer {
+                    name,
+                    is_default,
+                    alias_for: None,
+                    paused: false,
+                })
             })
             .collect();
 
@@ -155,18 +231,22 @@ mod platform {
     }
 
     /// Send raw bytes to a named printer via the Win32 spooler API.
-    pub fn send_raw(printer: &str, data: &[u8]) -> Result<(), String> {
+    pub fn send_raw(printer: &str, data: &[u8]) -> Result<(), Error> {
         let printer_hstring = HSTRING::from(printer);
         let mut handle = PRINTER_HANDLE::default();
 
         // Open the printer.
         unsafe {
-            OpenPrinterW(PCWSTR(printer_hstring.as_ptr()), &mut handle, None)
-                .map_err(|e| format!("OpenPrinterW failed: {e}"))?;
+            OpenPrinterW(PCWSTR(printer_hstring.as_ptr()), &mut handle, None).map_err(|e| {
+                Error::new(
+                    ErrorCode::PrinterNotFound,
+                    format!("OpenPrinterW failed: {e}"),
+                )
+            })?;
         }
 
         // Guard to ensure ClosePrinter runs even on early errors.
-        let result = (|| -> Result<(), String> {
+        let result = (|| -> Result<(), Error> {
             // DOC_INFO_1W fields are PWSTR. We cast from HSTRING's const pointer;
             // the spooler only reads through these pointers so this is safe.
             let doc_name = HSTRING::from("Dazzle Raw Print");
@@ -181,13 +261,13 @@ mod platform {
             // StartDocPrinterW returns a job ID (non-zero on success).
             let job_id = unsafe { StartDocPrinterW(handle, 1, &doc_info) };
             if job_id == 0 {
-                return Err("StartDocPrinterW failed (returned 0)".into());
+                return Err(spooler("StartDocPrinterW failed (returned 0)".into()));
             }
 
             unsafe {
                 StartPagePrinter(handle)
                     .ok()
-                    .map_err(|e| format!("StartPagePrinter failed: {e}"))?;
+                    .map_err(|e| spooler(format!("StartPagePrinter failed: {e}")))?;
             }
 
             // Write the data in full.
@@ -200,23 +280,23 @@ mod platform {
                     &mut bytes_written,
                 )
                 .ok()
-                .map_err(|e| format!("WritePrinter failed: {e}"))?;
+                .map_err(|e| spooler(format!("WritePrinter failed: {e}")))?;
             }
 
             if (bytes_written as usize) != data.len() {
-                return Err(format!(
+                return Err(spooler(format!(
                     "WritePrinter: only wrote {bytes_written} of {} bytes",
                     data.len()
-                ));
+                )));
             }
 
             unsafe {
                 EndPagePrinter(handle)
                     .ok()
-                    .map_err(|e| format!("EndPagePrinter failed: {e}"))?;
+                    .map_err(|e| spooler(format!("EndPagePrinter failed: {e}")))?;
                 EndDocPrinter(handle)
                     .ok()
-                    .map_err(|e| format!("EndDocPrinter failed: {e}"))?;
+                    .map_err(|e| spooler(format!("EndDocPrinter failed: {e}")))?;
             }
 
             Ok(())
@@ -229,6 +309,20 @@ mod platform {
         result
     }
 
+    /// The spooler answers if it can enumerate printers.
+    pub fn check_spooler() -> Result<(), Error> {
+        discover().map(|_| ())
+    }
+
+    /// Not queried on Windows; a stopped printer shows up as failing jobs.
+    pub fn printer_state(_printer: &str) -> Option<PrinterState> {
+        None
+    }
+
+    fn spooler(message: String) -> Error {
+        Error::new(ErrorCode::SpoolerError, message)
+    }
+
     /// Retrieve the name of the default printer, if any.
     fn get_default_printer_name() -> Option<String> {
         // First call: get required buffer length (in chars, including null).
@@ -254,12 +348,165 @@ mod platform {
     }
 }
 
+/// What the spooler says a printer is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrinterState {
+    Idle,
+    Printing,
+    /// Disabled or paused in the spooler, so jobs queue up without printing.
+    Stopped,
+}
+
 // ─── Public re-exports ──────────────────────────────────────────────────────
 
-pub fn discover() -> Result<Vec<Printer>, String> {
+pub fn discover() -> Result<Vec<Printer>, Error> {
     platform::discover()
 }
 
-pub fn send_raw(printer: &str, data: &[u8]) -> Result<(), String> {
-    platform::send_raw(printer, data)
+/// Whether the system spooler is up and answering.
+pub fn check_spooler() -> Result<(), Error> {
+    platform::check_spooler()
+}
+
+/// The spooler's state for `printer`, where the platform reports one.
+pub fn printer_state(printer: &str) -> Option<PrinterState> {
+    platform::printer_state(printer)
+}
+
+/// Send raw bytes to a printer. `tcp://host:port` targets are written
+/// straight to the network printer, bypassing the system spooler.
+///
+/// `dazzle://` printers on other stations are only reachable through the job
+/// pipeline, which forwards them.
+pub fn send_raw(printer: &str, data: &[u8]) -> Result<(), Error> {
+    if crate::peers::is_peer(printer) {
+        return Err(Error::new(
+            ErrorCode::InvalidRequest,
+            format!("{printer} is on another Dazzle station and can only be printed to as a job"),
+        ));
+    }
+    match printer.strip_prefix("tcp://") {
+        Some(address) => send_tcp(address, data),
+        None => platform::send_raw(printer, data),
+    }
+}
+
+// ─── Network printers ───────────────────────────────────────────────────────
+
+/// Port used when a `tcp://` address doesn't name one (JetDirect/raw).
+const DEFAULT_TCP_PORT: u16 = 9100;
+
+fn send_tcp(address: &str, data: &[u8]) -> Result<(), Error> {
+    use std::io::Write;
+    use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+    use std::time::Duration;
+
+    let address = address.trim_end_matches('/');
+    let addrs: Vec<_> = address
+        .to_socket_addrs()
+        .or_else(|_| (address, DEFAULT_TCP_PORT).to_socket_addrs())
+        .map_err(|e| {
+            Error::new(
+                ErrorCode::PrinterNotFound,
+                format!("Invalid printer address {address}: {e}"),
+            )
+        })?
+        .collect();
+
+    let offline = |message: String| Error::new(ErrorCode::PrinterOffline, message);
+    let mut last_error = offline(format!("No addresses found for {address}"));
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, Duration::from_secs(5)) {
+            Ok(mut stream) => {
+                stream
+                    .set_write_timeout(Some(Duration::from_secs(30)))
+                    .map_err(|e| Error::internal(e.to_string()))?;
+                stream
+                    .write_all(data)
+                    .and_then(|()| stream.flush())
+                    .map_err(|e| offline(format!("Failed to send to {addr}: {e}")))?;
+                let _ = stream.shutdown(Shutdown::Write);
+                return Ok(());
+            }
+            Err(e) => last_error = offline(format!("Failed to connect to {addr}: {e}")),
+        }
+    }
+
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    #[test]
+    fn tcp_targets_bypass_the_spooler() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let reader = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).unwrap();
+            received
+        });
+
+        send_raw(&format!("tcp://127.0.0.1:{port}"), b"^XA^FDtcp^XZ").unwrap();
+
+        assert_eq!(reader.join().unwrap(), b"^XA^FDtcp^XZ");
+    }
+
+    #[test]
+    fn unreachable_tcp_target_is_an_error() {
+        // Bind then drop to get a port nothing listens on
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let err = send_raw(&format!("tcp://127.0.0.1:{port}"), b"^XA^XZ").unwrap_err();
+        assert_eq!(err.code, ErrorCode::PrinterOffline);
+        assert!(err.message.contains("Failed to connect"));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn lp_errors_are_classified() {
+        use platform::lp_error_code;
+
+        assert_eq!(
+            lp_error_code("lp: The printer or class does not exist."),
+            ErrorCode::PrinterNotFound
+        );
+        assert_eq!(
+            lp_error_code("lp: Destination \"ZebraRaw\" is not accepting jobs."),
+            ErrorCode::PrinterOffline
+        );
+        assert_eq!(
+            lp_error_code("lp: Unable to connect to server"),
+            ErrorCode::SpoolerError
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn lpstat_printer_state_is_parsed() {
+        use platform::parse_printer_state;
+
+        assert_eq!(
+            parse_printer_state("printer ZebraRaw is idle.  enabled since Mon 01 Jan\n"),
+            Some(PrinterState::Idle)
+        );
+        assert_eq!(
+            parse_printer_state("printer ZebraRaw now printing ZebraRaw-42.  enabled since Mon\n"),
+            Some(PrinterState::Printing)
+        );
+        assert_eq!(
+            parse_printer_state("printer ZebraRaw disabled since Mon 01 Jan -\n\tPaused\n"),
+            Some(PrinterState::Stopped)
+        );
+        assert_eq!(parse_printer_state(""), None);
+    }
 }
@@ -0,0 +1,513 @@
+//! Agent mode: keep an outbound long-poll connection to a relay server and
+//! print the jobs it hands out, for stations a cloud app can't reach on
+//! localhost.
+//!
+//! The protocol is two endpoints under the configured relay URL, both
+//! authenticated with the station key as a bearer token:
+//!
+//! - `GET jobs/next` waits for a job and answers `200` with
+//!   `{ "id": "...", "printer": "..." | null, "data": "<base64>" }`, or `204`
+//!   when none arrived before the relay's own timeout.
+//! - `POST jobs/{id}/status` receives `{ "status", "job_id", "error" }` once
+//!   the job has printed or failed.
+//!
+//! Jobs are printed one at a time through [`jobs::run`], in the order the relay
+//! sends them. While printing is paused the agent stops polling, and a job
+//! that arrives just as printing is paused waits for it to resume rather
+//! than being reported as failed.
+
+use crate::config::RelayConfig;
+use crate::error::ErrorCode;
+use crate::jobs::{self, JobStatus};
+use crate::limits::Limits;
+use crate::AppState;
+use base64::Engine;
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Longest a single poll may take. Relays should answer `204` well before.
+const POLL_TIMEOUT: Duration = Duration::from_secs(90);
+/// Wait after the first failed poll, doubled up to [`MAX_BACKOFF`].
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Attempts at reporting a job's outcome before giving up on it.
+const REPORT_ATTEMPTS: u32 = 3;
+
+/// A job as the relay sends it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireJob {
+    pub id: String,
+    #[serde(default)]
+    pub printer: Option<String>,
+    /// Base64 of the raw printer data.
+    pub data: String,
+}
+
+/// A job received from the relay, ready to print.
+#[derive(Debug)]
+pub struct RelayJob {
+    pub id: String,
+    pub printer: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// The outcome of a relay job, sent back to the relay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub status: JobStatus,
+    /// The local job id, if a job was created.
+    pub job_id: Option<String>,
+    pub error: Option<String>,
+}
+
+impl Report {
+    fn failed(error: String) -> Self {
+        Self {
+            status: JobStatus::Failed,
+            job_id: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Pull and print jobs from the relay in `config` until cancelled.
+pub async fn run(state: Arc<AppState>, config: RelayConfig, cancel: CancellationToken) {
+    let fallback = config.printer.clone();
+    agent(&config, &state.limits, cancel, MIN_BACKOFF, |job| {
+        let state = state.clone();
+        let fallback = fallback.clone();
+        async move { print(&state, job, fallback).await }
+    })
+    .await;
+}
+
+async fn print(state: &Arc<AppState>, job: RelayJob, fallback: Option<String>) -> Report {
+    let printer = job.printer.or(fallback).or_else(|| {
+        state
+            .config
+            .read()
+            .ok()
+            .and_then(|c| c.selected_printer.clone())
+    });
+    let Some(printer) = printer else {
+        return Report::failed("No printer selected".to_string());
+    };
+
+    loop {
+        match jobs::run(state, printer.clone(), job.data.clone()).await {
+            Ok(job) => {
+                return Report {
+                    status: job.status,
+                    job_id: Some(job.id),
+                    error: job.error,
+                }
+            }
+            // Refusals aren't the job's fault, so keep it until it can print
+            Err(e) if e.code == ErrorCode::PrintingPaused => {
+                log::info!("Relay job {} waiting for printing to resume", job.id);
+                state.limits.wait_resumed().await;
+            }
+            Err(e) if e.code == ErrorCode::TooManyJobs => {
+                let wait = e.retry_after.unwrap_or(1);
+                tokio::time::sleep(Duration::from_secs(wait)).await;
+            }
+            Err(e) => return Report::failed(e.to_string()),
+        }
+    }
+}
+
+/// The poll, print, report loop, with printing left to `print`. Polling
+/// stops while `limits` has printing paused.
+async fn agent<F, Fut>(
+    config: &RelayConfig,
+    limits: &Limits,
+    cancel: CancellationToken,
+    min_backoff: Duration,
+    print: F,
+) where
+    F: Fn(RelayJob) -> Fut,
+    Fut: Future<Output = Report>,
+{
+    let client = match reqwest::Client::builder().timeout(POLL_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Relay agent disabled: failed to create HTTP client: {e}");
+            return;
+        }
+    };
+
+    log::info!("Relay agent polling {}", config.url);
+    let mut backoff = min_backoff;
+
+    loop {
+        if limits.is_paused() {
+            log::info!("Relay agent waiting for printing to resume");
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = limits.wait_resumed() => {}
+            }
+        }
+
+        let polled = tokio::select! {
+            _ = cancel.cancelled() => break,
+            polled = poll(&client, config) => polled,
+        };
+
+        match polled {
+            Ok(Some(job)) => {
+                backoff = min_backoff;
+                let id = job.id.clone();
+                log::info!("Relay job {id}: {} bytes", job.data.len());
+                let report = print(job).await;
+                report_outcome(&client, config, &id, &report, min_backoff).await;
+            }
+            Ok(None) => backoff = min_backoff,
+            Err(e) => {
+                log::warn!("Relay poll failed, retrying in {:?}: {e}", backoff);
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tokio::time::sleep(backoff) => {}
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+
+    log::info!("Relay agent stopped");
+}
+
+/// Wait for the next job. `None` means the relay had nothing to hand out.
+async fn poll(client: &reqwest::Client, config: &RelayConfig) -> Result<Option<RelayJob>, String> {
+    let response = client
+        .get(endpoint(&config.url, &["jobs", "next"])?)
+        .bearer_auth(&config.station_key)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let status = response.status();
+    if status == StatusCode::NO_CONTENT {
+        return Ok(None);
+    }
+    if !status.is_success() {
+        return Err(format!("Relay answered {status}"));
+    }
+
+    let body = response.bytes().await.map_err(|e| e.to_string())?;
+    let job: WireJob =
+        serde_json::from_slice(&body).map_err(|e| format!("Invalid job from relay: {e}"))?;
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(&job.data)
+        .map_err(|e| format!("Invalid data in relay job {}: {e}", job.id))?;
+
+    Ok(Some(RelayJob {
+        id: job.id,
+        printer: job.printer,
+        data,
+    }))
+}
+
+async fn report_outcome(
+    client: &reqwest::Client,
+    config: &RelayConfig,
+    id: &str,
+    report: &Report,
+    retry_delay: Duration,
+) {
+    for attempt in 1..=REPORT_ATTEMPTS {
+        match send_report(client, config, id, report).await {
+            Ok(()) => return,
+            Err(e) if attempt == REPORT_ATTEMPTS => {
+                log::error!("Failed to report relay job {id}: {e}");
+            }
+            Err(e) => {
+                log::warn!("Failed to report relay job {id}, retrying: {e}");
+                tokio::time::sleep(retry_delay).await;
+            }
+        }
+    }
+}
+
+async fn send_report(
+    client: &reqwest::Client,
+    config: &RelayConfig,
+    id: &str,
+    report: &Report,
+) -> Result<(), String> {
+    let body = serde_json::to_vec(report).map_err(|e| e.to_string())?;
+    let response = client
+        .post(endpoint(&config.url, &["jobs", id, "status"])?)
+        .bearer_auth(&config.station_key)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(format!("Relay answered {status}"))
+    }
+}
+
+/// `base` with `segments` appended as escaped path segments.
+fn endpoint(base: &str, segments: &[&str]) -> Result<Url, String> {
+    let mut url = Url::parse(base).map_err(|e| format!("Invalid relay URL {base}: {e}"))?;
+    url.path_segments_mut()
+        .map_err(|_| format!("Invalid relay URL {base}"))?
+        .pop_if_empty()
+        .extend(segments);
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        extract::{Path, State},
+        http::{header, HeaderMap},
+        response::{IntoResponse, Response},
+        routing::{get, post},
+        Json, Router,
+    };
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use tokio::sync::Notify;
+
+    /// Printer and data of every job the agent printed.
+    type Printed = Arc<Mutex<Vec<(Option<String>, Vec<u8>)>>>;
+
+    /// A stand-in relay: hands queued jobs to the station with the right key
+    /// and records the reports that come back.
+    #[derive(Clone, Default)]
+    struct StandIn {
+        key: String,
+        queue: Arc<Mutex<VecDeque<WireJob>>>,
+        reports: Arc<Mutex<Vec<(String, Report)>>>,
+        added: Arc<Notify>,
+    }
+
+    impl StandIn {
+        async fn start(key: &str) -> (Self, String) {
+            let relay = Self {
+                key: key.to_string(),
+                ..Self::default()
+            };
+            let app = Router::new()
+                .route("/relay/jobs/next", get(next_handler))
+                .route("/relay/jobs/{id}/status", post(status_handler))
+                .with_state(relay.clone());
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+            (relay, format!("http://{addr}/relay"))
+        }
+
+        fn push(&self, id: &str, printer: Option<&str>, data: &[u8]) {
+            self.queue.lock().unwrap().push_back(WireJob {
+                id: id.to_string(),
+                printer: printer.map(str::to_string),
+                data: base64::engine::general_purpose::STANDARD.encode(data),
+            });
+            self.added.notify_one();
+        }
+
+        fn authorized(&self, headers: &HeaderMap) -> bool {
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                == Some(format!("Bearer {}", self.key).as_str())
+        }
+    }
+
+    async fn next_handler(State(relay): State<StandIn>, headers: HeaderMap) -> Response {
+        if !relay.authorized(&headers) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        loop {
+            if let Some(job) = relay.queue.lock().unwrap().pop_front() {
+                return Json(job).into_response();
+            }
+            let wait = tokio::time::timeout(Duration::from_millis(200), relay.added.notified());
+            if wait.await.is_err() {
+                return StatusCode::NO_CONTENT.into_response();
+            }
+        }
+    }
+
+    async fn status_handler(
+        State(relay): State<StandIn>,
+        Path(id): Path<String>,
+        headers: HeaderMap,
+        Json(report): Json<Report>,
+    ) -> StatusCode {
+        if !relay.authorized(&headers) {
+            return StatusCode::UNAUTHORIZED;
+        }
+        relay.reports.lock().unwrap().push((id, report));
+        StatusCode::NO_CONTENT
+    }
+
+    fn config(url: &str, key: &str) -> RelayConfig {
+        RelayConfig {
+            enabled: true,
+            url: url.to_string(),
+            station_key: key.to_string(),
+            printer: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn agent_prints_relay_jobs_and_reports_back() {
+        let (relay, url) = StandIn::start("station-7").await;
+        relay.push("r1", Some("ZebraA"), b"^XA^FDone^FS^XZ");
+        relay.push("r2", Some("JammedZebra"), b"^XA^FDtwo^FS^XZ");
+
+        let printed: Printed = Arc::default();
+        let cancel = CancellationToken::new();
+        let agent = {
+            let printed = printed.clone();
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                let config = config(&url, "station-7");
+                let limits = Limits::default();
+                agent(&config, &limits, cancel, Duration::from_millis(10), |job| {
+                    let printed = printed.clone();
+                    async move {
+                        let jammed = job.printer.as_deref() == Some("JammedZebra");
+                        printed.lock().unwrap().push((job.printer, job.data));
+                        if jammed {
+                            Report::failed("Printer is jammed".to_string())
+                        } else {
+                            Report {
+                                status: JobStatus::Completed,
+                                job_id: Some(format!("local-{}", job.id)),
+                                error: None,
+                            }
+                        }
+                    }
+                })
+                .await
+            })
+        };
+
+        // A job queued while the agent is already waiting
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        relay.push("r3", None, b"^XA^FDthree^FS^XZ");
+
+        for _ in 0..100 {
+            if relay.reports.lock().unwrap().len() == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        cancel.cancel();
+        agent.await.unwrap();
+
+        let printed = printed.lock().unwrap();
+        assert_eq!(printed.len(), 3);
+        assert_eq!(printed[0].0.as_deref(), Some("ZebraA"));
+        assert_eq!(printed[0].1, b"^XA^FDone^FS^XZ");
+        assert_eq!(printed[2].0, None);
+
+        let reports = relay.reports.lock().unwrap();
+        let ids: Vec<_> = reports.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, ["r1", "r2", "r3"]);
+        assert!(matches!(reports[0].1.status, JobStatus::Completed));
+        assert_eq!(reports[0].1.job_id.as_deref(), Some("local-r1"));
+        assert!(matches!(reports[1].1.status, JobStatus::Failed));
+        assert_eq!(reports[1].1.error.as_deref(), Some("Printer is jammed"));
+    }
+
+    #[tokio::test]
+    async fn agent_stops_polling_while_paused() {
+        let (relay, url) = StandIn::start("station-7").await;
+        relay.push("r1", None, b"^XA^XZ");
+
+        let limits = Arc::new(Limits::default());
+        limits.set_paused(true);
+        let cancel = CancellationToken::new();
+        let agent = {
+            let limits = limits.clone();
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                let config = config(&url, "station-7");
+                agent(
+                    &config,
+                    &limits,
+                    cancel,
+                    Duration::from_millis(10),
+                    |_| async {
+                        Report {
+                            status: JobStatus::Completed,
+                            job_id: None,
+                            error: None,
+                        }
+                    },
+                )
+                .await
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(relay.queue.lock().unwrap().len(), 1);
+
+        limits.set_paused(false);
+        for _ in 0..100 {
+            if !relay.reports.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        cancel.cancel();
+        agent.await.unwrap();
+        assert!(relay.queue.lock().unwrap().is_empty());
+        assert_eq!(relay.reports.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn wrong_station_key_is_refused() {
+        let (relay, url) = StandIn::start("station-7").await;
+        relay.push("r1", None, b"^XA^XZ");
+
+        let result = poll(&reqwest::Client::new(), &config(&url, "nope")).await;
+
+        assert!(result.unwrap_err().contains("401"));
+        assert_eq!(relay.queue.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn empty_poll_is_not_an_error() {
+        let (_relay, url) = StandIn::start("station-7").await;
+        let result = poll(&reqwest::Client::new(), &config(&url, "station-7")).await;
+        assert!(result.unwrap().is_none());
+    }
+
+    #[test]
+    fn endpoint_appends_escaped_segments() {
+        assert_eq!(
+            endpoint(
+                "https://relay.example.com/stations/",
+                &["jobs", "a b", "status"]
+            )
+            .unwrap()
+            .as_str(),
+            "https://relay.example.com/stations/jobs/a%20b/status"
+        );
+        assert_eq!(
+            endpoint("https://relay.example.com", &["jobs", "next"])
+                .unwrap()
+                .as_str(),
+            "https://relay.example.com/jobs/next"
+        );
+        assert!(endpoint("not a url", &["jobs"]).is_err());
+    }
+}
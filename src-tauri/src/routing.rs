@@ -0,0 +1,281 @@
+//! Rule-based routing for `POST /print`: pick the printer from who sent a job
+//! and what it contains, instead of what the client asked for.
+//!
+//! Rules are checked in configured order and the first match wins. A job that
+//! matches no rule goes to the requested (or selected) printer as usual.
+
+use crate::config::RoutingRule;
+use axum::http::{header, HeaderMap};
+use regex::bytes::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// What a rule can look at for one incoming job.
+pub struct Request<'a> {
+    pub headers: &'a HeaderMap,
+    pub query: &'a [(String, String)],
+    pub data: &'a [u8],
+}
+
+/// A rule's payload pattern, compiled once when the config is loaded. An
+/// invalid pattern still loads, so one typo in the config file doesn't lose
+/// the rest of it, but it never matches and `set_config` refuses it.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    source: String,
+    regex: Result<Regex, regex::Error>,
+}
+
+impl Pattern {
+    pub fn new(source: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            regex: Regex::new(source),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Why the pattern doesn't compile, if it doesn't.
+    pub fn error(&self) -> Option<&regex::Error> {
+        self.regex.as_ref().err()
+    }
+
+    /// Whether `data` matches. Runs on the raw bytes, so binary payloads
+    /// aren't copied into a string first.
+    pub fn is_match(&self, data: &[u8]) -> bool {
+        self.regex.as_ref().is_ok_and(|regex| regex.is_match(data))
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|source| Self::new(&source))
+    }
+}
+
+/// The first rule matching `request`, if any.
+pub fn route<'r>(rules: &'r [RoutingRule], request: &Request) -> Option<&'r RoutingRule> {
+    rules.iter().find(|rule| matches(rule, request))
+}
+
+fn matches(rule: &RoutingRule, request: &Request) -> bool {
+    if let Some(origin) = &rule.origin {
+        let given = request
+            .headers
+            .get(header::ORIGIN)
+            .and_then(|v| v.to_str().ok());
+        if !given.is_some_and(|given| origin_matches(origin, given)) {
+            return false;
+        }
+    }
+
+    let headers_match = rule.headers.iter().all(|(name, expected)| {
+        request
+            .headers
+            .get_all(name.as_str())
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .any(|value| value_matches(expected, value))
+    });
+    if !headers_match {
+        return false;
+    }
+
+    let query_match = rule.query.iter().all(|(name, expected)| {
+        request
+            .query
+            .iter()
+            .any(|(key, value)| key == name && value_matches(expected, value))
+    });
+    if !query_match {
+        return false;
+    }
+
+    let size = request.data.len();
+    if rule.min_size.is_some_and(|min| size < min) || rule.max_size.is_some_and(|max| size > max) {
+        return false;
+    }
+
+    rule.pattern
+        .as_ref()
+        .map_or(true, |pattern| pattern.is_match(request.data))
+}
+
+fn value_matches(expected: &str, value: &str) -> bool {
+    expected == "*" || expected == value
+}
+
+/// `expected` is either a full origin (`https://app.example.com`) compared
+/// as-is, or a host where a leading `*.` matches any subdomain.
+pub(crate) fn origin_matches(expected: &str, origin: &str) -> bool {
+    if expected.contains("://") {
+        return expected.trim_end_matches('/').eq_ignore_ascii_case(origin);
+    }
+
+    let host = origin.split_once("://").map_or(origin, |(_, rest)| rest);
+    let host = match host.rsplit_once(':') {
+        // Keep IPv6 literals intact, only strip a trailing port
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    };
+
+    match expected.strip_prefix("*.") {
+        Some(domain) => host
+            .to_ascii_lowercase()
+            .ends_with(&format!(".{}", domain.to_ascii_lowercase())),
+        None => host.eq_ignore_ascii_case(expected),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn rule(name: &str, printer: &str) -> RoutingRule {
+        RoutingRule {
+            name: name.to_string(),
+            printer: printer.to_string(),
+            ..RoutingRule::default()
+        }
+    }
+
+    fn routed<'r>(
+        rules: &'r [RoutingRule],
+        headers: &[(&'static str, &str)],
+        query: &[(&str, &str)],
+        data: &[u8],
+    ) -> Option<&'r str> {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            header_map.append(*name, value.parse().unwrap());
+        }
+        let query: Vec<(String, String)> = query
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let request = Request {
+            headers: &header_map,
+            query: &query,
+            data,
+        };
+        route(rules, &request).map(|r| r.name.as_str())
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = vec![
+            RoutingRule {
+                origin: Some("returns.example.com".to_string()),
+                ..rule("returns", "ReturnsBench")
+            },
+            RoutingRule {
+                pattern: Some(Pattern::new(r"\^PW812")),
+                ..rule("four-inch", "Zebra4in")
+            },
+            rule("catch-all", "ZebraRaw"),
+        ];
+
+        let returns = [("origin", "https://returns.example.com")];
+        assert_eq!(
+            routed(&rules, &returns, &[], b"^XA^PW812^XZ"),
+            Some("returns")
+        );
+        assert_eq!(routed(&rules, &[], &[], b"^XA^PW812^XZ"), Some("four-inch"));
+        assert_eq!(routed(&rules, &[], &[], b"^XA^PW406^XZ"), Some("catch-all"));
+    }
+
+    #[test]
+    fn no_rules_means_no_route() {
+        assert_eq!(routed(&[], &[], &[], b"^XA^XZ"), None);
+    }
+
+    #[test]
+    fn origin_matches_hosts_wildcards_and_full_origins() {
+        assert!(origin_matches(
+            "returns.example.com",
+            "https://returns.example.com"
+        ));
+        assert!(origin_matches(
+            "returns.example.com",
+            "http://returns.example.com:8080"
+        ));
+        assert!(!origin_matches(
+            "returns.example.com",
+            "https://evilreturns.example.com"
+        ));
+        assert!(origin_matches("*.example.com", "https://a.b.example.com"));
+        assert!(!origin_matches("*.example.com", "https://example.com"));
+        assert!(origin_matches(
+            "https://app.example.com",
+            "https://app.example.com"
+        ));
+        assert!(!origin_matches(
+            "https://app.example.com",
+            "http://app.example.com"
+        ));
+        assert!(origin_matches("[::1]", "http://[::1]:3000"));
+    }
+
+    #[test]
+    fn headers_and_query_params_must_all_match() {
+        let rules = vec![RoutingRule {
+            headers: BTreeMap::from([("X-Dazzle-Tag".to_string(), "pharmacy".to_string())]),
+            query: BTreeMap::from([("station".to_string(), "*".to_string())]),
+            ..rule("pharmacy", "LockedZebra")
+        }];
+
+        let tagged = [("x-dazzle-tag", "pharmacy")];
+        assert_eq!(
+            routed(&rules, &tagged, &[("station", "3")], b"^XA^XZ"),
+            Some("pharmacy")
+        );
+        assert_eq!(routed(&rules, &tagged, &[], b"^XA^XZ"), None);
+        assert_eq!(
+            routed(
+                &rules,
+                &[("x-dazzle-tag", "retail")],
+                &[("station", "3")],
+                b"^XA^XZ"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn size_limits_are_inclusive() {
+        let rules = vec![RoutingRule {
+            min_size: Some(4),
+            max_size: Some(6),
+            ..rule("medium", "ZebraRaw")
+        }];
+
+        assert_eq!(routed(&rules, &[], &[], b"abc"), None);
+        assert_eq!(routed(&rules, &[], &[], b"abcd"), Some("medium"));
+        assert_eq!(routed(&rules, &[], &[], b"abcdef"), Some("medium"));
+        assert_eq!(routed(&rules, &[], &[], b"abcdefg"), None);
+    }
+
+    #[test]
+    fn invalid_pattern_never_matches() {
+        let rules = vec![RoutingRule {
+            pattern: Some(Pattern::new("^PW(")),
+            ..rule("broken", "ZebraRaw")
+        }];
+        assert_eq!(routed(&rules, &[], &[], b"^PW812"), None);
+    }
+}
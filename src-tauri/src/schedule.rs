@@ -0,0 +1,470 @@
+//! Jobs sent with `?at=` or `?delay=` on `POST /print`, held until they're
+//! due, such as end-of-day manifests that should print with nobody at the
+//! browser.
+//!
+//! The schedule is saved to `scheduled.json` in the config directory on
+//! every change, so jobs survive a restart. Jobs that came due while Dazzle
+//! wasn't running print as soon as it starts. A due job goes through the
+//! normal pipeline with the id it was given when it was scheduled, and only
+//! leaves the schedule once that's been saved, so a restart can't print it
+//! twice. At most [`MAX_SCHEDULED`] jobs wait at once.
+
+use crate::error::{Error, ErrorCode};
+use crate::jobs::{self, JobContext, JobStatus, PrintJob};
+use crate::{config, server, AppState};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::Emitter;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::sync::Notify;
+
+/// Longest the runner sleeps before looking at the clock again, so a
+/// machine waking from sleep catches up soon after.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+/// Wait before trying again when due jobs couldn't be taken off the saved
+/// schedule.
+const SAVE_RETRY: Duration = Duration::from_secs(5);
+/// Jobs that can wait on the schedule at once. Each keeps its data, and the
+/// whole schedule is rewritten on every change.
+pub const MAX_SCHEDULED: usize = 100;
+
+#[derive(Default)]
+pub struct Schedule {
+    entries: Mutex<Vec<Entry>>,
+    /// Wakes the runner when a job is added or cancelled.
+    changed: Notify,
+    /// Where the schedule is saved. `None` keeps it in memory only.
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    /// The job as listed, with status `scheduled` and the time it's due in
+    /// `scheduled_for`.
+    job: PrintJob,
+    /// Where to print, after routing rules and groups. With several, the
+    /// job's id becomes the parent id of one job per printer.
+    printers: Vec<String>,
+    /// The data to print, base64-encoded.
+    data: String,
+    #[serde(default)]
+    callback: Option<String>,
+    #[serde(default)]
+    forwarded: bool,
+}
+
+impl Schedule {
+    /// The schedule saved in the config directory.
+    pub fn load() -> Self {
+        Self::load_from(config::config_dir().join("scheduled.json"))
+    }
+
+    fn load_from(path: PathBuf) -> Self {
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+                log::warn!("Failed to parse scheduled jobs at {}: {e}", path.display());
+                Vec::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                log::warn!("Failed to read scheduled jobs at {}: {e}", path.display());
+                Vec::new()
+            }
+        };
+        Self {
+            entries: Mutex::new(entries),
+            changed: Notify::new(),
+            path: Some(path),
+        }
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, Vec<Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn save(&self, entries: &[Entry]) -> Result<(), Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json =
+            serde_json::to_string_pretty(entries).map_err(|e| Error::internal(e.to_string()))?;
+        std::fs::write(path, json)
+            .map_err(|e| Error::internal(format!("Failed to save scheduled jobs: {e}")))
+    }
+
+    fn add(&self, entry: Entry) -> Result<(), Error> {
+        let mut entries = self.entries();
+        if entries.len() >= MAX_SCHEDULED {
+            return Err(Error::new(
+                ErrorCode::TooManyJobs,
+                format!("{MAX_SCHEDULED} jobs are already scheduled"),
+            ));
+        }
+        entries.push(entry);
+        if let Err(e) = self.save(&entries) {
+            entries.pop();
+            return Err(e);
+        }
+        self.changed.notify_one();
+        Ok(())
+    }
+
+    /// Jobs waiting to print, soonest first.
+    pub fn jobs(&self) -> Vec<PrintJob> {
+        let mut jobs: Vec<PrintJob> = self.entries().iter().map(|e| e.job.clone()).collect();
+        jobs.sort_by_key(|job| job.scheduled_for);
+        jobs
+    }
+
+    /// The job a request with `key` scheduled, if it hasn't printed yet.
+    pub fn find_key(&self, key: &str) -> Option<PrintJob> {
+        self.entries()
+            .iter()
+            .find(|e| e.job.idempotency_key.as_deref() == Some(key))
+            .map(|e| e.job.clone())
+    }
+
+    fn remove(&self, id: &str) -> Result<PrintJob, Error> {
+        let mut entries = self.entries();
+        let Some(index) = entries.iter().position(|e| e.job.id == id) else {
+            return Err(Error::new(
+                ErrorCode::JobNotFound,
+                format!("No scheduled job {id}"),
+            ));
+        };
+        let entry = entries.remove(index);
+        if let Err(e) = self.save(&entries) {
+            entries.insert(index, entry);
+            return Err(e);
+        }
+        self.changed.notify_one();
+        Ok(entry.job)
+    }
+
+    /// When the next job is due.
+    fn next_due(&self) -> Option<u64> {
+        self.entries()
+            .iter()
+            .filter_map(|e| e.job.scheduled_for)
+            .min()
+    }
+
+    /// Take the jobs due at `now` off the schedule. If the shorter schedule
+    /// can't be saved they're left on it, so they don't print again after a
+    /// restart.
+    fn take_due(&self, now: u64) -> Result<Vec<Entry>, Error> {
+        let mut entries = self.entries();
+        let is_due = |e: &Entry| e.job.scheduled_for.map_or(true, |at| at <= now);
+        if !entries.iter().any(is_due) {
+            return Ok(Vec::new());
+        }
+        let waiting: Vec<Entry> = entries.iter().filter(|e| !is_due(e)).cloned().collect();
+        self.save(&waiting)?;
+        let (due, waiting) = entries.drain(..).partition(is_due);
+        *entries = waiting;
+        Ok(due)
+    }
+}
+
+/// When a job asked for with `at` (RFC 3339) or `delay` (seconds) should
+/// print, or `None` to print it now.
+pub fn due_time(at: Option<&str>, delay: Option<u64>, now: u64) -> Result<Option<u64>, Error> {
+    match (at, delay) {
+        (None, None) => Ok(None),
+        (Some(_), Some(_)) => Err(Error::new(
+            ErrorCode::InvalidRequest,
+            "Use either at or delay, not both",
+        )),
+        (None, Some(delay)) => Ok(Some(now.saturating_add(delay))),
+        (Some(at), None) => {
+            let at = OffsetDateTime::parse(at, &Rfc3339).map_err(|e| {
+                Error::new(
+                    ErrorCode::InvalidRequest,
+                    format!("Invalid at {at:?}, expected an RFC 3339 time: {e}"),
+                )
+            })?;
+            match u64::try_from(at.unix_timestamp()) {
+                Ok(at) if at >= now => Ok(Some(at)),
+                _ => Err(Error::new(
+                    ErrorCode::InvalidRequest,
+                    "The time in at has already passed",
+                )),
+            }
+        }
+    }
+}
+
+/// `secs` since the Unix epoch as an RFC 3339 time in UTC.
+pub fn format_time(secs: u64) -> String {
+    i64::try_from(secs)
+        .ok()
+        .and_then(|secs| OffsetDateTime::from_unix_timestamp(secs).ok())
+        .and_then(|time| time.format(&Rfc3339).ok())
+        .unwrap_or_default()
+}
+
+/// Schedule `data` to print on `printers` at `due`, with `context.id` as
+/// the job's id. Returns the job as it will be listed.
+pub fn add(
+    state: &AppState,
+    printers: Vec<String>,
+    data: &[u8],
+    context: JobContext,
+    due: u64,
+) -> Result<PrintJob, Error> {
+    let job = PrintJob {
+        id: context.id.unwrap_or_else(jobs::generate_id),
+        printer: printers.join(", "),
+        timestamp: jobs::now_secs(),
+        status: JobStatus::Scheduled,
+        zpl_preview: Some(String::from_utf8_lossy(&data[..data.len().min(200)]).to_string()),
+        error: None,
+        error_code: None,
+        dispatched_to: None,
+        rule: context.rule,
+        parent_id: None,
+        remote_job_id: None,
+        idempotency_key: context.idempotency_key,
+        scheduled_for: Some(due),
+        metadata: context.metadata,
+    };
+    state.schedule.add(Entry {
+        job: job.clone(),
+        printers,
+        data: base64::engine::general_purpose::STANDARD.encode(data),
+        callback: context.callback,
+        forwarded: context.forwarded,
+    })?;
+
+    log::info!(
+        "Scheduled job {} on {} for {}",
+        job.id,
+        job.printer,
+        format_time(due)
+    );
+    state.app_handle.emit("print-job", &job).ok();
+    Ok(job)
+}
+
+/// Take a job off the schedule before it prints.
+pub fn cancel(state: &AppState, id: &str) -> Result<PrintJob, Error> {
+    let job = state.schedule.remove(id)?;
+    log::info!("Cancelled scheduled job {id}");
+    state
+        .app_handle
+        .emit("scheduled-job-cancelled", &job.id)
+        .ok();
+    Ok(job)
+}
+
+/// Print scheduled jobs as they come due, for the life of the app. Runs
+/// apart from the listeners, so restarting them doesn't start a second one.
+pub async fn run(state: Arc<AppState>) {
+    loop {
+        let now = jobs::now_secs();
+        let wait = match state.schedule.take_due(now) {
+            Ok(due) => {
+                for entry in due {
+                    tokio::spawn(print(state.clone(), entry));
+                }
+                state
+                    .schedule
+                    .next_due()
+                    .map(|at| Duration::from_secs(at.saturating_sub(now)).min(MAX_SLEEP))
+                    .unwrap_or(MAX_SLEEP)
+            }
+            Err(e) => {
+                log::warn!("Scheduled jobs are due but stay queued: {e}");
+                SAVE_RETRY
+            }
+        };
+
+        tokio::select! {
+            _ = state.schedule.changed.notified() => {}
+            _ = tokio::time::sleep(wait) => {}
+        }
+    }
+}
+
+async fn print(state: Arc<AppState>, entry: Entry) {
+    let Entry {
+        mut job,
+        printers,
+        data,
+        callback,
+        forwarded,
+    } = entry;
+    let mut context = JobContext {
+        rule: job.rule.clone(),
+        callback: callback.clone(),
+        forwarded,
+        idempotency_key: job.idempotency_key.clone(),
+        scheduled_for: job.scheduled_for,
+        metadata: job.metadata.clone(),
+        ..JobContext::default()
+    };
+    log::info!("Printing scheduled job {}", job.id);
+
+    let result = match base64::engine::general_purpose::STANDARD.decode(&data) {
+        Err(e) => Err(Error::internal(format!(
+            "Scheduled job data is corrupt: {e}"
+        ))),
+        Ok(data) => {
+            if let [printer] = printers.as_slice() {
+                context.id = Some(job.id.clone());
+                jobs::run_with_context(&state, printer.clone(), data, context)
+                    .await
+                    .map(drop)
+            } else {
+                context.parent_id = Some(job.id.clone());
+                server::fan_out(&state, printers, data, context)
+                    .await
+                    .map(drop)
+            }
+        }
+    };
+
+    // Printing was refused before a job was created, so record why here
+    if let Err(e) = result {
+        log::error!("Scheduled job {} failed: {e}", job.id);
+        job.status = JobStatus::Failed;
+        job.error = Some(e.to_string());
+        job.error_code = Some(e.code);
+        jobs::record(&state, &job, callback.as_deref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, due: u64) -> Entry {
+        Entry {
+            job: PrintJob {
+                id: id.to_string(),
+                printer: "ZebraRaw".to_string(),
+                timestamp: 0,
+                status: JobStatus::Scheduled,
+                zpl_preview: None,
+                error: None,
+                error_code: None,
+                dispatched_to: None,
+                rule: None,
+                parent_id: None,
+                remote_job_id: None,
+                idempotency_key: Some(format!("key-{id}")),
+                scheduled_for: Some(due),
+                metadata: Default::default(),
+            },
+            printers: vec!["ZebraRaw".to_string()],
+            data: "XlhBXlha".to_string(),
+            callback: None,
+            forwarded: false,
+        }
+    }
+
+    #[test]
+    fn due_time_from_at_or_delay() {
+        let now = 1_790_000_000;
+        assert_eq!(due_time(None, None, now).unwrap(), None);
+        assert_eq!(due_time(None, Some(90), now).unwrap(), Some(now + 90));
+        assert_eq!(
+            due_time(Some("2026-10-18T17:00:00Z"), None, now).unwrap(),
+            Some(1_792_342_800)
+        );
+        assert_eq!(
+            due_time(Some("2026-10-18T19:00:00+02:00"), None, now).unwrap(),
+            Some(1_792_342_800)
+        );
+
+        for (at, delay) in [
+            (Some("tomorrow"), None),
+            (Some("2020-01-01T00:00:00Z"), None),
+            (Some("2026-10-18T17:00:00Z"), Some(5)),
+        ] {
+            let error = due_time(at, delay, now).unwrap_err();
+            assert_eq!(error.code, ErrorCode::InvalidRequest);
+        }
+        assert_eq!(format_time(1_792_342_800), "2026-10-18T17:00:00Z");
+    }
+
+    #[test]
+    fn due_jobs_are_taken_in_order() {
+        let schedule = Schedule::default();
+        schedule.add(entry("late", 300)).unwrap();
+        schedule.add(entry("early", 100)).unwrap();
+
+        let ids: Vec<String> = schedule.jobs().into_iter().map(|j| j.id).collect();
+        assert_eq!(ids, ["early", "late"]);
+        assert_eq!(schedule.next_due(), Some(100));
+        assert_eq!(schedule.find_key("key-late").unwrap().id, "late");
+
+        assert!(schedule.take_due(99).unwrap().is_empty());
+        let due = schedule.take_due(100).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].job.id, "early");
+        assert_eq!(schedule.next_due(), Some(300));
+    }
+
+    #[test]
+    fn cancelled_jobs_leave_the_schedule() {
+        let schedule = Schedule::default();
+        schedule.add(entry("a", 100)).unwrap();
+
+        assert_eq!(schedule.remove("a").unwrap().id, "a");
+        assert_eq!(
+            schedule.remove("a").unwrap_err().code,
+            ErrorCode::JobNotFound
+        );
+        assert!(schedule.jobs().is_empty());
+    }
+
+    #[test]
+    fn schedule_survives_a_restart() {
+        let path =
+            std::env::temp_dir().join(format!("dazzle-schedule-{}.json", std::process::id()));
+        let schedule = Schedule {
+            path: Some(path.clone()),
+            ..Schedule::default()
+        };
+        schedule.add(entry("a", 100)).unwrap();
+        schedule.add(entry("b", 200)).unwrap();
+        schedule.take_due(100).unwrap();
+
+        let jobs = Schedule::load_from(path.clone()).jobs();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, "b");
+        assert_eq!(jobs[0].scheduled_for, Some(200));
+    }
+
+    #[test]
+    fn due_jobs_stay_until_the_schedule_is_saved() {
+        let dir = std::env::temp_dir().join(format!("dazzle-unsaved-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let schedule = Schedule {
+            path: Some(dir.join("scheduled.json")),
+            ..Schedule::default()
+        };
+        schedule.add(entry("a", 100)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(schedule.take_due(100).is_err());
+        assert_eq!(schedule.jobs().len(), 1);
+        assert_eq!(schedule.next_due(), Some(100));
+    }
+
+    #[test]
+    fn schedule_is_capped() {
+        let schedule = Schedule::default();
+        for i in 0..MAX_SCHEDULED {
+            schedule.add(entry(&i.to_string(), 100)).unwrap();
+        }
+        let error = schedule.add(entry("one-more", 100)).unwrap_err();
+        assert_eq!(error.code, ErrorCode::TooManyJobs);
+    }
+}
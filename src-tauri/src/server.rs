@@ -1,167 +1,529 @@
-use crate::printing;
+use crate::error::{Error, ErrorCode};
+use crate::jobs::{self, JobContext, JobStatus};
+use crate::{
+    browser_print, dedupe, health, holds, idempotency, ipp, lan, limits, lpd, metrics, peers,
+    printing, qz, relay, routing, schedule, tls, webhooks,
+};
 use axum::{
     body::Bytes,
-    extract::{DefaultBodyLimit, Query, State},
-    http::StatusCode,
-    response::Json,
-    routing::{get, post},
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    middleware,
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, post},
     Router,
 };
+use axum_server::tls_rustls::RustlsConfig;
 use base64::Engine;
 use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use tauri::Emitter;
+use tokio::io::AsyncReadExt;
 use tokio::net::TcpListener;
 use tokio_util::sync::CancellationToken;
 use tower_http::cors::CorsLayer;
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PrintJob {
-    pub id: String,
-    pub printer: String,
-    pub timestamp: u64,
-    pub status: JobStatus,
-    pub zpl_preview: Option<String>,
-    pub error: Option<String>,
+/// `printer` isn't here: it may be repeated, so it's read from the raw
+/// query pairs instead.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PrintQuery {
+    /// `base64` to decode the body before printing.
+    encoding: Option<String>,
+    /// URL to POST the finished job to, if `webhooks.allow_callbacks` is on.
+    callback: Option<String>,
+    /// Print even if the same job was just sent.
+    #[serde(default)]
+    force: bool,
+    /// Print at this RFC 3339 time instead of now.
+    at: Option<String>,
+    /// Print this many seconds from now instead of now.
+    delay: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum JobStatus {
-    Printing,
-    Completed,
-    Failed,
+#[derive(Serialize, ToSchema)]
+struct PrintResponse {
+    job_id: String,
 }
 
-#[derive(Deserialize)]
-struct PrintQuery {
-    printer: Option<String>,
-    encoding: Option<String>,
+/// Response for a job sent to several printers. `job_id` is the parent id
+/// shared by every child job.
+#[derive(Serialize, ToSchema)]
+struct FanOutResponse {
+    job_id: String,
+    jobs: Vec<FanOutResult>,
 }
 
-#[derive(Serialize)]
-struct PrintResponse {
+#[derive(Serialize, ToSchema)]
+struct FanOutResult {
+    printer: String,
     job_id: String,
+    status: JobStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<ErrorCode>,
 }
 
-#[derive(Serialize)]
-struct StatusResponse {
-    status: String,
-    version: String,
+/// Response for a job scheduled with `at` or `delay`.
+#[derive(Serialize, ToSchema)]
+struct ScheduledResponse {
+    job_id: String,
+    /// When the job will print, as an RFC 3339 time in UTC.
+    scheduled_for: String,
 }
 
-fn generate_id() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let nanos = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_nanos();
-    format!("{nanos:x}")
+/// The documented 200 body of `POST /print`: one job, or the outcome on
+/// every printer for a job sent to several.
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+#[allow(dead_code)] // Only describes the OpenAPI document
+enum Printed {
+    Job(PrintResponse),
+    FanOut(FanOutResponse),
 }
 
-fn now_secs() -> u64 {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs()
+/// The documented 500 body of `POST /print`: the error, or the outcome on
+/// every printer when a job sent to several failed on all of them.
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+#[allow(dead_code)] // Only describes the OpenAPI document
+enum PrintFailed {
+    Error(Error),
+    FanOut(FanOutResponse),
 }
 
+#[derive(Serialize, ToSchema)]
+struct StatusResponse {
+    status: String,
+    version: String,
+}
+
+/// Print the body. The printer comes from the first routing rule that
+/// matches, then `?printer=`, then the printer selected in the app.
+///
+/// Repeating `printer` or naming a printer group sends the job to every
+/// printer at once; see [`fan_out`]. `?callback=` adds a URL the finished
+/// job is POSTed to, like a configured webhook. A repeated `Idempotency-Key`
+/// replays the earlier result instead of printing again, and with the
+/// duplicate guard on, an identical job sent moments ago is refused with 409
+/// unless `?force=true`. With `?at=` or `?delay=` the job is put on the
+/// schedule and answered with 202 straight away.
+#[utoipa::path(
+    post,
+    path = "/print",
+    summary = "Print a job",
+    description = "Prints the body on the printer picked by the routing rules, `printer` or the \
+        app's selection. Repeating `printer` or naming a group prints on every printer at once.",
+    params(
+        PrintQuery,
+        ("printer" = Option<Vec<String>>, Query, description = "Printer, alias, pool or group to print on. Repeat to print on several."),
+        ("reference" = Option<String>, Query, description = "Your id for the label, such as an order number"),
+        ("tags" = Option<Vec<String>>, Query, description = "Comma-separated tags. May be repeated."),
+        ("source" = Option<String>, Query, description = "The app or integration sending the job"),
+        ("user" = Option<String>, Query, description = "Who printed the job"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Replay the earlier result for a repeated key instead of printing again"),
+    ),
+    request_body(
+        content = String,
+        content_type = "text/plain",
+        description = "ZPL, or base64-encoded data with `encoding=base64`",
+    ),
+    responses(
+        (status = 200, description = "Printed", body = Printed),
+        (status = 202, description = "Scheduled to print later", body = ScheduledResponse),
+        (status = 207, description = "Printed on some of several printers", body = FanOutResponse),
+        (status = 400, description = "Invalid request", body = Error),
+        (status = 401, description = "LAN access needs the token", body = Error),
+        (status = 403, description = "Origin or address not allowed", body = Error),
+        (status = 404, description = "Printer not found", body = Error),
+        (status = 409, description = "Duplicate of a recent job", body = Error),
+        (status = 413, description = "Body larger than 10 MB", body = String, content_type = "text/plain"),
+        (status = 429, description = "Rate limited; see `Retry-After`", body = Error),
+        (status = 500, description = "Printing failed", body = PrintFailed),
+        (status = 503, description = "Printer offline", body = Error),
+    ),
+)]
 async fn print_handler(
     State(state): State<Arc<crate::AppState>>,
     Query(query): Query<PrintQuery>,
+    Query(params): Query<Vec<(String, String)>>,
+    headers: HeaderMap,
     body: Bytes,
-) -> Result<Json<PrintResponse>, (StatusCode, String)> {
+) -> Result<Response, Error> {
     if body.is_empty() {
-        return Err((StatusCode::BAD_REQUEST, "Empty ZPL body".to_string()));
+        return Err(Error::new(ErrorCode::EmptyBody, "Empty ZPL body"));
+    }
+
+    let idempotency_key = headers
+        .get(idempotency::HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .map(str::to_string);
+    // Held until the job is in the history, so retries wait and replay it
+    let _key_guard = match &idempotency_key {
+        Some(key) => Some(state.idempotency.lock(key).await),
+        None => None,
+    };
+    if let Some(key) = &idempotency_key {
+        if let Some(job) = state.schedule.find_key(key) {
+            log::info!(
+                "Replaying scheduled job {} for idempotency key {key:?}",
+                job.id
+            );
+            return Ok(replayed(scheduled_response(&job)));
+        }
+        let window = state.config.read().map(|c| c.idempotency_window_secs)?;
+        let previous = state
+            .print_jobs
+            .read()
+            .map(|jobs| idempotency::previous(&jobs, key, window, jobs::now_secs()))?;
+        if let Some(first) = previous.first() {
+            log::info!("Replaying job {} for idempotency key {key:?}", first.id);
+            let response = match first.parent_id.clone() {
+                Some(parent_id) => fan_out_response(parent_id, previous),
+                None => job_response(first.clone()),
+            };
+            return Ok(replayed(response));
+        }
     }
 
     let data = if query.encoding.as_deref() == Some("base64") {
         base64::engine::general_purpose::STANDARD
             .decode(&body)
-            .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid base64: {e}")))?
+            .map_err(|e| Error::new(ErrorCode::InvalidEncoding, format!("Invalid base64: {e}")))?
     } else {
         body.to_vec()
     };
+    let due = schedule::due_time(query.at.as_deref(), query.delay, jobs::now_secs())?;
 
-    let printer = query
-        .printer
-        .or_else(|| {
-            state
-                .config
-                .read()
-                .ok()
-                .and_then(|c| c.selected_printer.clone())
-        })
-        .ok_or((StatusCode::BAD_REQUEST, "No printer selected".to_string()))?;
+    let (rule, printers, dedupe_window) = {
+        let config = state.config.read()?;
+        if let Some(callback) = &query.callback {
+            if !config.webhooks.allow_callbacks {
+                return Err(Error::new(
+                    ErrorCode::InvalidRequest,
+                    "Callback URLs are disabled",
+                ));
+            }
+            if !webhooks::is_valid_url(callback) {
+                return Err(Error::new(
+                    ErrorCode::InvalidRequest,
+                    format!("Invalid callback URL: {callback}"),
+                ));
+            }
+        }
+        let request = routing::Request {
+            headers: &headers,
+            query: &params,
+            data: &data,
+        };
+        let rule = routing::route(&config.rules, &request).cloned();
 
-    let job_id = generate_id();
-    let zpl_preview = String::from_utf8_lossy(&data[..data.len().min(200)]).to_string();
+        let requested: Vec<String> = match &rule {
+            Some(rule) => vec![rule.printer.clone()],
+            None => params
+                .iter()
+                .filter(|(key, _)| key == "printer")
+                .map(|(_, printer)| printer.clone())
+                .collect(),
+        };
+        let printers = if requested.is_empty() {
+            config.selected_printer.iter().cloned().collect()
+        } else {
+            config.expand_groups(&requested)
+        };
+        let dedupe_window = (config.dedupe.enabled && !query.force)
+            .then(|| Duration::from_secs(config.dedupe.window_secs));
+        (rule, printers, dedupe_window)
+    };
 
-    let mut job = PrintJob {
-        id: job_id.clone(),
-        printer: printer.clone(),
-        timestamp: now_secs(),
-        status: JobStatus::Printing,
-        zpl_preview: Some(zpl_preview),
-        error: None,
+    let mut context = JobContext {
+        callback: query.callback,
+        forwarded: peers::is_forwarded(&headers),
+        idempotency_key,
+        metadata: jobs::JobMetadata::from_request(&headers, &params),
+        ..JobContext::default()
     };
+    if let Some(rule) = rule {
+        log::info!("Routing rule {:?} sends job to {}", rule.name, rule.printer);
+        context.rule = Some(rule.name);
+    }
 
-    state.app_handle.emit("print-job", &job).ok();
+    if printers.is_empty() {
+        return Err(Error::new(
+            ErrorCode::NoPrinterSelected,
+            "No printer selected",
+        ));
+    }
 
-    // Run blocking print operation off the async runtime
-    let print_result = tokio::task::spawn_blocking(move || printing::send_raw(&printer, &data))
-        .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Print task panicked: {e}"),
+    // The job's id, or the parent id when fanning out
+    let job_id = jobs::generate_id();
+    let fingerprint = dedupe::fingerprint(&printers, &data);
+    if let Some(window) = dedupe_window {
+        if let Err(original) = state.dedupe.claim(fingerprint, &job_id, window) {
+            log::warn!("Refused duplicate of job {original}");
+            return Err(Error::new(
+                ErrorCode::DuplicateJob,
+                "Duplicate of a job sent moments ago, add ?force=true to print it again",
             )
-        })?;
-
-    match print_result {
-        Ok(()) => {
-            job.status = JobStatus::Completed;
-            log::info!("Printed to {}", job.printer);
-        }
-        Err(e) => {
-            job.status = JobStatus::Failed;
-            job.error = Some(e.clone());
-            log::error!("Print failed for {}: {e}", job.printer);
+            .with_job_id(original));
         }
     }
 
-    // Store job
-    if let Ok(mut jobs) = state.print_jobs.write() {
-        jobs.insert(0, job.clone());
-        jobs.truncate(100);
+    if let Some(due) = due {
+        context.id = Some(job_id.clone());
+        return match schedule::add(&state, printers, &data, context, due) {
+            Ok(job) => Ok(scheduled_response(&job)),
+            Err(e) => {
+                state.dedupe.release(&fingerprint, &job_id);
+                Err(e)
+            }
+        };
     }
 
-    state.app_handle.emit("print-job", &job).ok();
+    let (failed, response) = if let [printer] = printers.as_slice() {
+        context.id = Some(job_id.clone());
+        let job = jobs::run_with_context(&state, printer.clone(), data, context).await?;
+        (matches!(job.status, JobStatus::Failed), job_response(job))
+    } else {
+        context.parent_id = Some(job_id.clone());
+        let jobs = fan_out(&state, printers, data, context).await?;
+        let failed = jobs.iter().all(|j| matches!(j.status, JobStatus::Failed));
+        (failed, fan_out_response(job_id.clone(), jobs))
+    };
+
+    // A failed job may be retried right away
+    if failed {
+        state.dedupe.release(&fingerprint, &job_id);
+    }
+    Ok(response)
+}
 
+/// The `POST /print` response for a job sent to a single printer.
+fn job_response(job: jobs::PrintJob) -> Response {
+    // Failures carry the job id too, so a forwarding station can mirror it
+    let job_id = [(peers::JOB_ID_HEADER, job.id.clone())];
     if matches!(job.status, JobStatus::Failed) {
-        Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            job.error.unwrap_or_default(),
-        ))
+        let code = job.error_code.unwrap_or(ErrorCode::SpoolerError);
+        let error = Error::new(code, job.error.unwrap_or_default()).with_job_id(job.id);
+        (job_id, error).into_response()
     } else {
-        Ok(Json(PrintResponse { job_id }))
+        (job_id, Json(PrintResponse { job_id: job.id })).into_response()
     }
 }
 
-async fn printers_handler() -> Result<Json<Vec<printing::Printer>>, (StatusCode, String)> {
-    tokio::task::spawn_blocking(printing::discover)
-        .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Task panicked: {e}"),
+/// The `POST /print` response for a job put on the schedule.
+fn scheduled_response(job: &jobs::PrintJob) -> Response {
+    let response = ScheduledResponse {
+        job_id: job.id.clone(),
+        scheduled_for: schedule::format_time(job.scheduled_for.unwrap_or_default()),
+    };
+    (
+        StatusCode::ACCEPTED,
+        [(peers::JOB_ID_HEADER, job.id.clone())],
+        Json(response),
+    )
+        .into_response()
+}
+
+/// Mark `response` as a replay of an earlier request's result.
+fn replayed(mut response: Response) -> Response {
+    response.headers_mut().insert(
+        idempotency::REPLAYED_HEADER,
+        axum::http::HeaderValue::from_static("true"),
+    );
+    response
+}
+
+/// Print the same data on several printers in parallel, one child job each,
+/// all sharing the parent id in `context`.
+pub(crate) async fn fan_out(
+    state: &Arc<crate::AppState>,
+    printers: Vec<String>,
+    data: Vec<u8>,
+    context: JobContext,
+) -> Result<Vec<jobs::PrintJob>, Error> {
+    let tasks: Vec<_> = printers
+        .into_iter()
+        .map(|printer| {
+            let state = state.clone();
+            let data = data.clone();
+            let context = context.clone();
+            tokio::spawn(
+                async move { jobs::run_with_context(&state, printer, data, context).await },
             )
-        })?
-        .map(Json)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+        })
+        .collect();
+
+    let mut jobs = Vec::new();
+    for task in tasks {
+        jobs.push(task.await??);
+    }
+
+    Ok(jobs)
 }
 
+/// The `POST /print` response for the child jobs of a fan-out. Answers 200
+/// if every printer succeeded, 207 if only some did and 500 if none did,
+/// always with the outcome per printer.
+fn fan_out_response(parent_id: String, jobs: Vec<jobs::PrintJob>) -> Response {
+    let results: Vec<FanOutResult> = jobs
+        .into_iter()
+        .map(|job| FanOutResult {
+            printer: job.printer,
+            job_id: job.id,
+            status: job.status,
+            error: job.error,
+            code: job.error_code,
+        })
+        .collect();
+
+    let failed = results
+        .iter()
+        .filter(|r| matches!(r.status, JobStatus::Failed))
+        .count();
+    let status = if failed == 0 {
+        StatusCode::OK
+    } else if failed == results.len() {
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::MULTI_STATUS
+    };
+
+    let response = FanOutResponse {
+        job_id: parent_id,
+        jobs: results,
+    };
+    (status, Json(response)).into_response()
+}
+
+/// Discovered printers followed by the configured aliases, each with the
+/// printer it points at in `alias_for`.
+#[utoipa::path(
+    get,
+    path = "/printers",
+    summary = "List printers",
+    description = "Discovered printers followed by the configured aliases.",
+    responses(
+        (status = 200, description = "Printers and aliases", body = Vec<printing::Printer>),
+        (status = 500, description = "Discovery failed", body = Error),
+    ),
+)]
+async fn printers_handler(
+    State(state): State<Arc<crate::AppState>>,
+) -> Result<Json<Vec<printing::Printer>>, Error> {
+    let mut printers = {
+        let state = state.clone();
+        tokio::task::spawn_blocking(move || metrics::discover(&state)).await??
+    };
+
+    let aliases = state
+        .config
+        .read()
+        .map(|c| c.aliases.clone())
+        .unwrap_or_default();
+    printers.extend(aliases.into_iter().map(|(name, target)| printing::Printer {
+        name,
+        is_default: false,
+        alias_for: Some(target),
+        paused: false,
+    }));
+    state.holds.mark(&mut printers);
+
+    Ok(Json(printers))
+}
+
+/// Hold new jobs for a printer, such as while its labels are changed. Jobs
+/// wait in the queue until it's resumed.
+#[utoipa::path(
+    post,
+    path = "/printers/{name}/pause",
+    summary = "Pause a printer",
+    params(("name" = String, Path, description = "Printer, alias or pool")),
+    responses(
+        (status = 200, description = "The printer is paused", body = holds::PrinterPaused),
+        (status = 404, description = "No such printer", body = Error),
+    ),
+)]
+async fn pause_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path(name): Path<String>,
+) -> Result<Json<holds::PrinterPaused>, Error> {
+    set_printer_paused(state, name, true).await
+}
+
+/// Print the jobs held for a printer and take new ones again.
+#[utoipa::path(
+    post,
+    path = "/printers/{name}/resume",
+    summary = "Resume a printer",
+    params(("name" = String, Path, description = "Printer, alias or pool")),
+    responses(
+        (status = 200, description = "The printer is printing again", body = holds::PrinterPaused),
+        (status = 404, description = "No such printer", body = Error),
+    ),
+)]
+async fn resume_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path(name): Path<String>,
+) -> Result<Json<holds::PrinterPaused>, Error> {
+    set_printer_paused(state, name, false).await
+}
+
+/// Jobs waiting to print at a later time, soonest first.
+#[utoipa::path(
+    get,
+    path = "/jobs/scheduled",
+    summary = "List scheduled jobs",
+    responses((status = 200, description = "Scheduled jobs", body = Vec<jobs::PrintJob>)),
+)]
+async fn scheduled_jobs_handler(
+    State(state): State<Arc<crate::AppState>>,
+) -> Json<Vec<jobs::PrintJob>> {
+    Json(state.schedule.jobs())
+}
+
+/// Take a job off the schedule before it prints.
+#[utoipa::path(
+    delete,
+    path = "/jobs/scheduled/{id}",
+    summary = "Cancel a scheduled job",
+    params(("id" = String, Path, description = "The job's id")),
+    responses(
+        (status = 200, description = "The cancelled job", body = jobs::PrintJob),
+        (status = 404, description = "No such scheduled job", body = Error),
+    ),
+)]
+async fn cancel_scheduled_job_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<jobs::PrintJob>, Error> {
+    schedule::cancel(&state, &id).map(Json)
+}
+
+async fn set_printer_paused(
+    state: Arc<crate::AppState>,
+    name: String,
+    paused: bool,
+) -> Result<Json<holds::PrinterPaused>, Error> {
+    tokio::task::spawn_blocking(move || {
+        let printer = holds::resolve(&state, &name)?;
+        Ok(Json(holds::set_paused(&state, &printer, paused)))
+    })
+    .await?
+}
+
+#[utoipa::path(
+    get,
+    path = "/status",
+    summary = "Check that Dazzle is running",
+    responses((status = 200, description = "Running", body = StatusResponse)),
+)]
 async fn status_handler() -> Json<StatusResponse> {
     Json(StatusResponse {
         status: "running".to_string(),
@@ -169,9 +531,79 @@ async fn status_handler() -> Json<StatusResponse> {
     })
 }
 
+/// The OpenAPI document for the HTTP API, built from the handlers' path
+/// attributes and the request and response types.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Dazzle",
+        description = "Print raw ZPL from the browser on local and network printers."
+    ),
+    servers((url = "/v1")),
+    paths(
+        print_handler,
+        printers_handler,
+        pause_handler,
+        resume_handler,
+        scheduled_jobs_handler,
+        cancel_scheduled_job_handler,
+        status_handler,
+        health_handler,
+        metrics_handler,
+        openapi_handler
+    ),
+    components(schemas(jobs::PrintJob))
+)]
+struct ApiDoc;
+
+/// Checks the spooler, the selected printer, the disk and the backlog. Unlike
+/// `/status`, answers 503 when something is broken.
+#[utoipa::path(
+    get,
+    path = "/health",
+    summary = "Check that printing works",
+    responses(
+        (status = 200, description = "Every check passed or only warned", body = health::Health),
+        (status = 503, description = "A check failed", body = health::Health),
+    ),
+)]
+async fn health_handler(State(state): State<Arc<crate::AppState>>) -> Result<Response, Error> {
+    let health = tokio::task::spawn_blocking(move || health::check(&state)).await?;
+    Ok((health.http_status(), Json(health)).into_response())
+}
+
+/// Counters for monitoring, in the Prometheus text format.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    summary = "Prometheus metrics",
+    responses((status = 200, description = "Metrics in the Prometheus text format", content_type = "text/plain")),
+)]
+async fn metrics_handler(State(state): State<Arc<crate::AppState>>) -> Response {
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        state.metrics.render(),
+    )
+        .into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    summary = "This OpenAPI document",
+    responses((status = 200, description = "OpenAPI 3.1 document")),
+)]
+async fn openapi_handler() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
 pub struct ServerHandle {
     task: tokio::task::JoinHandle<()>,
     cancel: CancellationToken,
+    mdns: Option<lan::Advertisement>,
 }
 
 impl ServerHandle {
@@ -181,6 +613,9 @@ impl ServerHandle {
 
     /// Gracefully shut down the server and wait for it to stop.
     pub async fn shutdown(self) {
+        if let Some(mdns) = self.mdns {
+            mdns.stop();
+        }
         self.cancel.cancel();
         // Wait up to 2 seconds for graceful shutdown
         let _ = tokio::time::timeout(std::time::Duration::from_secs(2), self.task).await;
@@ -188,38 +623,479 @@ impl ServerHandle {
 }
 
 /// Bind the port and start the Axum server. Returns a handle that supports
-/// graceful shutdown. Fails immediately if the port can't be bound.
+/// graceful shutdown. Fails immediately if the port can't be bound on any
+/// address; addresses that fail while others succeed are reported through
+/// `server-warning` events instead.
+///
+/// By default both loopback addresses are bound, since some clients resolve
+/// `localhost` to `::1` first. See [`AppConfig::listen_addrs`] for LAN mode.
+///
+/// When HTTPS is enabled the same router is also served over TLS on
+/// `tls_port`. A failure there is logged but doesn't take down plain HTTP.
+///
+/// With Browser Print compatibility on, the router also gets Zebra's routes
+/// and is served on Browser Print's own ports, since its JS hardcodes them.
+/// QZ Tray compatibility likewise gets its own WebSocket listeners on QZ's
+/// ports.
+///
+/// [`AppConfig::listen_addrs`]: crate::config::AppConfig::listen_addrs
 pub async fn start(state: Arc<crate::AppState>) -> Result<ServerHandle, String> {
-    let port = state.config.read().map_err(|e| e.to_string())?.port;
+    let (port, tls_port, raw_port, lpd_port, browser_print, qz, relay, addrs, advertise) = {
+        let config = state.config.read().map_err(|e| e.to_string())?;
+        (
+            config.port,
+            config.tls_enabled.then_some(config.tls_port),
+            config.raw.enabled.then_some(config.raw.port),
+            config.lpd.enabled.then_some(config.lpd.port),
+            config
+                .browser_print
+                .enabled
+                .then(|| config.browser_print.clone()),
+            config.qz.enabled.then(|| config.qz.clone()),
+            config.relay.enabled.then(|| config.relay.clone()),
+            config.listen_addrs(),
+            config.lan.enabled && config.lan.advertise_mdns,
+        )
+    };
 
-    let router = Router::new()
+    // The unversioned paths stay as aliases for existing integrations
+    let api = Router::new()
         .route("/print", post(print_handler))
         .route("/printers", get(printers_handler))
+        .route("/printers/{name}/pause", post(pause_handler))
+        .route("/printers/{name}/resume", post(resume_handler))
+        .route("/jobs/scheduled", get(scheduled_jobs_handler))
+        .route("/jobs/scheduled/{id}", delete(cancel_scheduled_job_handler))
         .route("/status", get(status_handler))
-        .layer(DefaultBodyLimit::max(10 * 1024 * 1024)) // 10 MB
+        .route("/health", get(health_handler))
+        .route("/metrics", get(metrics_handler))
+        .route("/openapi.json", get(openapi_handler));
+    let mut router = Router::new()
+        .nest("/v1", api.clone())
+        .merge(api)
+        .route("/ipp/print/{name}", post(ipp::handler));
+    if browser_print.is_some() {
+        router = router.merge(browser_print::routes());
+    }
+    let router = router
+        .layer(DefaultBodyLimit::max(jobs::MAX_JOB_SIZE))
+        .layer(middleware::from_fn_with_state(state.clone(), limits::guard))
+        .layer(middleware::from_fn_with_state(state.clone(), lan::guard))
         .layer(CorsLayer::permissive())
         .with_state(state.clone());
 
-    let listener = TcpListener::bind(format!("127.0.0.1:{port}"))
-        .await
-        .map_err(|e| format!("Failed to bind port {port}: {e}"))?;
+    let qz_router = qz::routes()
+        .layer(middleware::from_fn_with_state(state.clone(), limits::guard))
+        .layer(middleware::from_fn_with_state(state.clone(), lan::guard))
+        .with_state(state.clone());
+
+    let mut listeners = bind_all(&state, &addrs, port).await?;
+
+    let mut tls_listeners = match tls_port {
+        Some(tls_port) => match bind_tls(&state, &addrs, tls_port).await {
+            Ok(tls_listeners) => Some(tls_listeners),
+            Err(e) => {
+                log::error!("HTTPS listener disabled: {e}");
+                None
+            }
+        },
+        None => None,
+    };
+
+    if let Some(browser_print) = &browser_print {
+        match bind_all(&state, &addrs, browser_print.port).await {
+            Ok(bp_listeners) => listeners.extend(bp_listeners),
+            Err(e) => log::error!("Browser Print HTTP listener disabled: {e}"),
+        }
+        if let Some((tls_listeners, _)) = &mut tls_listeners {
+            match bind_all(&state, &addrs, browser_print.tls_port).await {
+                Ok(bp_listeners) => {
+                    for listener in bp_listeners {
+                        match listener.into_std() {
+                            Ok(listener) => tls_listeners.push(listener),
+                            Err(e) => log::error!("Browser Print HTTPS listener failed: {e}"),
+                        }
+                    }
+                }
+                Err(e) => log::error!("Browser Print HTTPS listener disabled: {e}"),
+            }
+        }
+    }
+
+    let mut qz_listeners = Vec::new();
+    let mut qz_tls_listeners = Vec::new();
+    if let Some(qz) = &qz {
+        match bind_all(&state, &addrs, qz.port).await {
+            Ok(listeners) => qz_listeners = listeners,
+            Err(e) => log::error!("QZ Tray listener disabled: {e}"),
+        }
+        if tls_listeners.is_some() {
+            match bind_all(&state, &addrs, qz.tls_port).await {
+                Ok(listeners) => {
+                    for listener in listeners {
+                        match listener.into_std() {
+                            Ok(listener) => qz_tls_listeners.push(listener),
+                            Err(e) => log::error!("QZ Tray secure listener failed: {e}"),
+                        }
+                    }
+                }
+                Err(e) => log::error!("QZ Tray secure listener disabled: {e}"),
+            }
+        }
+    }
+
+    let raw_listeners = match raw_port {
+        Some(raw_port) => match bind_all(&state, &addrs, raw_port).await {
+            Ok(raw_listeners) => raw_listeners,
+            Err(e) => {
+                log::error!("Raw print listener disabled: {e}");
+                Vec::new()
+            }
+        },
+        None => Vec::new(),
+    };
+
+    let lpd_listeners = match lpd_port {
+        Some(lpd_port) => match bind_all(&state, &addrs, lpd_port).await {
+            Ok(lpd_listeners) => lpd_listeners,
+            Err(e) => {
+                log::error!("LPD listener disabled: {e}");
+                Vec::new()
+            }
+        },
+        None => Vec::new(),
+    };
+
+    let mdns = if advertise {
+        let tls_port = tls_listeners.as_ref().and(tls_port);
+        match lan::Advertisement::start(port, tls_port) {
+            Ok(mdns) => Some(mdns),
+            Err(e) => {
+                log::error!("mDNS advertisement disabled: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
 
-    log::info!("HTTP server listening on 127.0.0.1:{port}");
     state.app_handle.emit("server-status", true).ok();
 
     let cancel = CancellationToken::new();
-    let cancel_clone = cancel.clone();
+    let mut servers = tokio::task::JoinSet::new();
 
-    let task = tokio::spawn(async move {
-        let shutdown = async move { cancel_clone.cancelled().await };
-        if let Err(e) = axum::serve(listener, router)
-            .with_graceful_shutdown(shutdown)
-            .await
-        {
-            log::error!("Server error: {e}");
+    for listener in listeners {
+        if let Ok(addr) = listener.local_addr() {
+            log::info!("HTTP server listening on {addr}");
+        }
+        servers.spawn(serve_http(listener, router.clone(), cancel.clone()));
+    }
+
+    if let Some((tls_listeners, config)) = tls_listeners {
+        for listener in tls_listeners {
+            if let Ok(addr) = listener.local_addr() {
+                log::info!("HTTPS server listening on {addr}");
+            }
+            servers.spawn(serve_tls(
+                listener,
+                config.clone(),
+                router.clone(),
+                cancel.clone(),
+            ));
+        }
+        for listener in qz_tls_listeners {
+            if let Ok(addr) = listener.local_addr() {
+                log::info!("QZ Tray secure WebSocket listening on {addr}");
+            }
+            servers.spawn(serve_tls(
+                listener,
+                config.clone(),
+                qz_router.clone(),
+                cancel.clone(),
+            ));
+        }
+    }
+
+    for listener in qz_listeners {
+        if let Ok(addr) = listener.local_addr() {
+            log::info!("QZ Tray WebSocket listening on {addr}");
+        }
+        servers.spawn(serve_http(listener, qz_router.clone(), cancel.clone()));
+    }
+
+    for listener in raw_listeners {
+        if let Ok(addr) = listener.local_addr() {
+            log::info!("Raw print listener on {addr}");
+        }
+        servers.spawn(serve_raw(listener, state.clone(), cancel.clone()));
+    }
+
+    for listener in lpd_listeners {
+        if let Ok(addr) = listener.local_addr() {
+            log::info!("LPD server listening on {addr}");
         }
+        servers.spawn(lpd::serve(listener, state.clone(), cancel.clone()));
+    }
+
+    if let Some(relay) = relay {
+        servers.spawn(relay::run(state.clone(), relay, cancel.clone()));
+    }
+
+    let task = tokio::spawn(async move {
+        servers.join_all().await;
         state.app_handle.emit("server-status", false).ok();
     });
 
-    Ok(ServerHandle { task, cancel })
+    Ok(ServerHandle { task, cancel, mdns })
+}
+
+/// How long a raw connection may sit idle before what it sent so far is
+/// printed. Most clients close the socket when done, but not all of them.
+const RAW_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Accept raw TCP connections until cancelled, like a JetDirect port.
+async fn serve_raw(listener: TcpListener, state: Arc<crate::AppState>, cancel: CancellationToken) {
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    tokio::spawn(handle_raw_connection(state.clone(), stream, peer));
+                }
+                Err(e) => log::warn!("Raw listener accept failed: {e}"),
+            },
+        }
+    }
+}
+
+/// Read one job's worth of bytes from a raw connection and print it on the
+/// configured raw printer.
+async fn handle_raw_connection(
+    state: Arc<crate::AppState>,
+    mut stream: tokio::net::TcpStream,
+    peer: SocketAddr,
+) {
+    let (lan, printer) = match state.config.read() {
+        Ok(config) => (
+            config.lan.clone(),
+            config
+                .raw
+                .printer
+                .clone()
+                .or_else(|| config.selected_printer.clone()),
+        ),
+        Err(_) => return,
+    };
+
+    if let Err((_, message)) = lan::check_address(&lan, peer.ip()) {
+        log::warn!("Rejected raw connection from {peer}: {message}");
+        return;
+    }
+
+    let mut data = Vec::new();
+    loop {
+        match tokio::time::timeout(RAW_IDLE_TIMEOUT, stream.read_buf(&mut data)).await {
+            Ok(Ok(0)) | Err(_) => break,
+            Ok(Ok(_)) if data.len() > jobs::MAX_JOB_SIZE => {
+                log::warn!(
+                    "Raw job from {peer} exceeds {} bytes, dropped",
+                    jobs::MAX_JOB_SIZE
+                );
+                return;
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                log::warn!("Raw connection from {peer} failed: {e}");
+                return;
+            }
+        }
+    }
+
+    if data.is_empty() {
+        return;
+    }
+
+    let Some(printer) = printer else {
+        log::error!("Raw job from {peer} dropped: no printer selected");
+        return;
+    };
+
+    log::info!("Raw job from {peer}: {} bytes", data.len());
+    if let Err(e) = jobs::run(&state, printer, data).await {
+        log::error!("Raw job from {peer} failed: {e}");
+    }
+}
+
+/// Bind `port` on each of `addrs`. Succeeds as long as at least one address
+/// could be bound; the failures for the rest are logged and emitted as
+/// `server-warning` events, one per address.
+async fn bind_all(
+    state: &crate::AppState,
+    addrs: &[IpAddr],
+    port: u16,
+) -> Result<Vec<TcpListener>, String> {
+    let mut listeners = Vec::new();
+    let mut errors = Vec::new();
+
+    for ip in addrs {
+        let addr = SocketAddr::new(*ip, port);
+        match TcpListener::bind(addr).await {
+            Ok(listener) => listeners.push(listener),
+            Err(e) => errors.push(format!("Failed to bind {addr}: {e}")),
+        }
+    }
+
+    if listeners.is_empty() {
+        return Err(errors.join("; "));
+    }
+
+    for error in errors {
+        log::warn!("{error}");
+        state.app_handle.emit("server-warning", &error).ok();
+    }
+
+    Ok(listeners)
+}
+
+async fn bind_tls(
+    state: &crate::AppState,
+    addrs: &[IpAddr],
+    port: u16,
+) -> Result<(Vec<std::net::TcpListener>, RustlsConfig), String> {
+    let config = tokio::task::spawn_blocking(rustls_config)
+        .await
+        .map_err(|e| format!("Certificate task panicked: {e}"))??;
+
+    let listeners = bind_all(state, addrs, port)
+        .await?
+        .into_iter()
+        .map(|l| l.into_std())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to prepare HTTPS listener: {e}"))?;
+
+    Ok((listeners, config))
+}
+
+fn rustls_config() -> Result<RustlsConfig, String> {
+    use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+
+    let material = tls::load_or_generate()?;
+
+    let certs = CertificateDer::pem_slice_iter(material.cert_chain_pem.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid certificate: {e}"))?;
+    let key = PrivateKeyDer::from_pem_slice(material.key_pem.as_bytes())
+        .map_err(|e| format!("Invalid private key: {e}"))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("TLS setup failed: {e}"))?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("TLS setup failed: {e}"))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(RustlsConfig::from_config(Arc::new(config)))
+}
+
+async fn serve_http(listener: TcpListener, router: Router, cancel: CancellationToken) {
+    let shutdown = async move { cancel.cancelled().await };
+    let service = router.into_make_service_with_connect_info::<SocketAddr>();
+    if let Err(e) = axum::serve(listener, service)
+        .with_graceful_shutdown(shutdown)
+        .await
+    {
+        log::error!("Server error: {e}");
+    }
+}
+
+async fn serve_tls(
+    listener: std::net::TcpListener,
+    config: RustlsConfig,
+    router: Router,
+    cancel: CancellationToken,
+) {
+    let handle = axum_server::Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        cancel.cancelled().await;
+        shutdown_handle.graceful_shutdown(Some(Duration::from_secs(1)));
+    });
+
+    let server = match axum_server::from_tcp_rustls(listener, config) {
+        Ok(server) => server,
+        Err(e) => {
+            log::error!("HTTPS server error: {e}");
+            return;
+        }
+    };
+
+    if let Err(e) = server
+        .handle(handle)
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+    {
+        log::error!("HTTPS server error: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openapi_document_covers_the_api() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        assert_eq!(doc["servers"][0]["url"], "/v1");
+        for path in [
+            "/print",
+            "/printers",
+            "/printers/{name}/pause",
+            "/jobs/scheduled",
+            "/jobs/scheduled/{id}",
+            "/status",
+            "/health",
+            "/metrics",
+            "/openapi.json",
+        ] {
+            assert!(doc["paths"][path].is_object(), "{path} missing");
+        }
+        let schemas = &doc["components"]["schemas"];
+        for schema in [
+            "PrintResponse",
+            "StatusResponse",
+            "PrintJob",
+            "Printer",
+            "Error",
+        ] {
+            assert!(schemas[schema].is_object(), "{schema} missing");
+        }
+
+        let responses = &doc["paths"]["/print"]["post"]["responses"];
+        for status in [
+            "200", "202", "207", "400", "401", "403", "404", "409", "413", "429", "500", "503",
+        ] {
+            assert!(responses[status].is_object(), "{status} missing");
+        }
+        for (schema, variants) in [("Printed", 2), ("PrintFailed", 2)] {
+            let one_of = schemas[schema]["oneOf"].as_array();
+            assert_eq!(one_of.map(Vec::len), Some(variants), "{schema}");
+        }
+
+        let params = doc["paths"]["/print"]["post"]["parameters"]
+            .as_array()
+            .unwrap();
+        let names: Vec<_> = params.iter().map(|p| p["name"].as_str().unwrap()).collect();
+        for name in [
+            "encoding",
+            "callback",
+            "force",
+            "printer",
+            "Idempotency-Key",
+        ] {
+            assert!(names.contains(&name), "{name} missing from {names:?}");
+        }
+    }
 }
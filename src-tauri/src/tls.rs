@@ -0,0 +1,253 @@
+//! Locally generated certificates for the optional HTTPS listener.
+//!
+//! On first use we create a private "Dazzle Local CA" and a `localhost` leaf
+//! certificate signed by it, and keep both in `<config dir>/dazzle/tls`. Only
+//! the CA needs to be trusted by browsers, so the leaf can be reissued freely.
+
+use rcgen::{
+    BasicConstraints, CertificateParams, DistinguishedName, DnType, ExtendedKeyUsagePurpose, IsCa,
+    Issuer, KeyPair, KeyUsagePurpose, SanType,
+};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const CA_CERT_FILE: &str = "ca.pem";
+const CA_KEY_FILE: &str = "ca.key";
+const LEAF_CERT_FILE: &str = "localhost.pem";
+const LEAF_KEY_FILE: &str = "localhost.key";
+
+const CA_VALIDITY_DAYS: i64 = 3650;
+/// Apple rejects user-trusted server certificates valid for more than 825 days,
+/// so stay well under that and reissue the leaf once it's a year old.
+const LEAF_VALIDITY_DAYS: i64 = 397;
+const LEAF_REISSUE_AFTER: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// PEM-encoded certificate chain and private key for the HTTPS listener.
+pub struct TlsMaterial {
+    pub cert_chain_pem: String,
+    pub key_pem: String,
+}
+
+pub fn tls_dir() -> PathBuf {
+    let path = crate::config::config_dir().join("tls");
+    if let Err(e) = std::fs::create_dir_all(&path) {
+        log::warn!("Failed to create TLS directory: {e}");
+    }
+    path
+}
+
+/// Path of the CA certificate that IT should install as trusted.
+pub fn ca_cert_path() -> PathBuf {
+    tls_dir().join(CA_CERT_FILE)
+}
+
+/// Load the stored CA and leaf certificate, generating whatever is missing.
+pub fn load_or_generate() -> Result<TlsMaterial, String> {
+    load_or_generate_in(&tls_dir())
+}
+
+/// Copy the CA certificate to `dest`, generating it first if needed.
+pub fn export_ca(dest: &Path) -> Result<(), String> {
+    let dir = tls_dir();
+    load_or_generate_in(&dir)?;
+    std::fs::copy(dir.join(CA_CERT_FILE), dest)
+        .map(|_| ())
+        .map_err(|e| format!("Failed to export CA certificate to {}: {e}", dest.display()))
+}
+
+fn load_or_generate_in(dir: &Path) -> Result<TlsMaterial, String> {
+    let (ca_cert_pem, ca_key_pem) = match read_pair(dir, CA_CERT_FILE, CA_KEY_FILE) {
+        Some(pair) => pair,
+        None => {
+            log::info!("Generating local CA in {}", dir.display());
+            let (cert, key) = generate_ca()?;
+            write_pair(dir, CA_CERT_FILE, &cert, CA_KEY_FILE, &key)?;
+            // A new CA invalidates any previously issued leaf
+            let _ = std::fs::remove_file(dir.join(LEAF_CERT_FILE));
+            (cert, key)
+        }
+    };
+
+    let leaf = read_pair(dir, LEAF_CERT_FILE, LEAF_KEY_FILE)
+        .filter(|_| !is_stale(&dir.join(LEAF_CERT_FILE)));
+
+    let (leaf_cert_pem, leaf_key_pem) = match leaf {
+        Some(pair) => pair,
+        None => {
+            log::info!("Issuing localhost certificate in {}", dir.display());
+            let (cert, key) = generate_leaf(&ca_key_pem)?;
+            write_pair(dir, LEAF_CERT_FILE, &cert, LEAF_KEY_FILE, &key)?;
+            (cert, key)
+        }
+    };
+
+    Ok(TlsMaterial {
+        cert_chain_pem: format!("{leaf_cert_pem}{ca_cert_pem}"),
+        key_pem: leaf_key_pem,
+    })
+}
+
+fn read_pair(dir: &Path, cert_file: &str, key_file: &str) -> Option<(String, String)> {
+    let cert = std::fs::read_to_string(dir.join(cert_file)).ok()?;
+    let key = std::fs::read_to_string(dir.join(key_file)).ok()?;
+    Some((cert, key))
+}
+
+fn write_pair(
+    dir: &Path,
+    cert_file: &str,
+    cert_pem: &str,
+    key_file: &str,
+    key_pem: &str,
+) -> Result<(), String> {
+    let key_path = dir.join(key_file);
+    std::fs::write(&key_path, key_pem)
+        .map_err(|e| format!("Failed to write {}: {e}", key_path.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(&key_path, std::fs::Permissions::from_mode(0o600));
+    }
+
+    let cert_path = dir.join(cert_file);
+    std::fs::write(&cert_path, cert_pem)
+        .map_err(|e| format!("Failed to write {}: {e}", cert_path.display()))
+}
+
+fn is_stale(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age > LEAF_REISSUE_AFTER)
+}
+
+fn validity(params: &mut CertificateParams, days: i64) {
+    let now = time::OffsetDateTime::now_utc();
+    // Backdate slightly so clients with a skewed clock still accept it
+    params.not_before = now - time::Duration::days(1);
+    params.not_after = now + time::Duration::days(days);
+}
+
+/// Subject and constraints of the local CA. Leaves are signed against these
+/// same params, so issuing only needs the stored CA key, not a cert parser.
+fn ca_params() -> CertificateParams {
+    let mut params = CertificateParams::default();
+    let mut dn = DistinguishedName::new();
+    dn.push(DnType::CommonName, "Dazzle Local CA");
+    dn.push(DnType::OrganizationName, "Dazzle");
+    params.distinguished_name = dn;
+    params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+    params.key_usages = vec![
+        KeyUsagePurpose::KeyCertSign,
+        KeyUsagePurpose::CrlSign,
+        KeyUsagePurpose::DigitalSignature,
+    ];
+    params
+}
+
+fn generate_ca() -> Result<(String, String), String> {
+    let key = KeyPair::generate().map_err(|e| format!("Failed to generate CA key: {e}"))?;
+
+    let mut params = ca_params();
+    validity(&mut params, CA_VALIDITY_DAYS);
+
+    let cert = params
+        .self_signed(&key)
+        .map_err(|e| format!("Failed to self-sign CA: {e}"))?;
+
+    Ok((cert.pem(), key.serialize_pem()))
+}
+
+fn generate_leaf(ca_key_pem: &str) -> Result<(String, String), String> {
+    let ca_key =
+        KeyPair::from_pem(ca_key_pem).map_err(|e| format!("Failed to load CA key: {e}"))?;
+    let issuer = Issuer::new(ca_params(), ca_key);
+
+    let key = KeyPair::generate().map_err(|e| format!("Failed to generate leaf key: {e}"))?;
+
+    let mut params = CertificateParams::new(vec!["localhost".to_string()])
+        .map_err(|e| format!("Invalid certificate name: {e}"))?;
+    params.subject_alt_names.extend([
+        SanType::IpAddress(std::net::Ipv4Addr::LOCALHOST.into()),
+        SanType::IpAddress(std::net::Ipv6Addr::LOCALHOST.into()),
+    ]);
+    params
+        .distinguished_name
+        .push(DnType::CommonName, "localhost");
+    params.key_usages = vec![
+        KeyUsagePurpose::DigitalSignature,
+        KeyUsagePurpose::KeyEncipherment,
+    ];
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+    validity(&mut params, LEAF_VALIDITY_DAYS);
+
+    let cert = params
+        .signed_by(&key, &issuer)
+        .map_err(|e| format!("Failed to sign localhost certificate: {e}"))?;
+
+    Ok((cert.pem(), key.serialize_pem()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dazzle-tls-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn generates_ca_and_leaf_on_first_run() {
+        let dir = temp_dir("first-run");
+
+        let material = load_or_generate_in(&dir).unwrap();
+
+        assert!(dir.join(CA_CERT_FILE).exists());
+        assert!(dir.join(CA_KEY_FILE).exists());
+        assert!(dir.join(LEAF_CERT_FILE).exists());
+        assert!(dir.join(LEAF_KEY_FILE).exists());
+        // Leaf followed by the CA
+        assert_eq!(
+            material.cert_chain_pem.matches("BEGIN CERTIFICATE").count(),
+            2
+        );
+        assert!(material.key_pem.contains("PRIVATE KEY"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reuses_stored_certificates() {
+        let dir = temp_dir("reuse");
+
+        let first = load_or_generate_in(&dir).unwrap();
+        let second = load_or_generate_in(&dir).unwrap();
+
+        assert_eq!(first.cert_chain_pem, second.cert_chain_pem);
+        assert_eq!(first.key_pem, second.key_pem);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_leaf_is_reissued_from_existing_ca() {
+        let dir = temp_dir("reissue");
+
+        load_or_generate_in(&dir).unwrap();
+        let ca_before = std::fs::read_to_string(dir.join(CA_CERT_FILE)).unwrap();
+        std::fs::remove_file(dir.join(LEAF_CERT_FILE)).unwrap();
+
+        let material = load_or_generate_in(&dir).unwrap();
+
+        let ca_after = std::fs::read_to_string(dir.join(CA_CERT_FILE)).unwrap();
+        assert_eq!(ca_before, ca_after);
+        assert!(material.cert_chain_pem.ends_with(&ca_after));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
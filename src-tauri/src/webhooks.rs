@@ -0,0 +1,372 @@
+//! Job completion webhooks: POST the finished [`PrintJob`] to configured URLs
+//! (and a per-request `callback`) so a backend learns whether a label really
+//! printed without the browser in the loop.
+//!
+//! Each delivery is signed with HMAC-SHA256 over the body when a secret is
+//! set, retried with backoff until it gets a 2xx, and kept in a short log.
+
+use crate::jobs::{JobStatus, PrintJob};
+use crate::AppState;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Emitter;
+
+/// Attempts per delivery before giving up.
+const MAX_ATTEMPTS: u32 = 5;
+/// Delay before the first retry, doubled after every failed attempt.
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many deliveries are kept in the log.
+const LOG_LEN: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Delivery {
+    pub id: String,
+    pub job_id: String,
+    pub url: String,
+    pub event: String,
+    pub timestamp: u64,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    /// HTTP status of the last response, if there was one.
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+}
+
+impl Delivery {
+    /// A pending delivery of `event` for `job_id` to `url`, with an id of its
+    /// own since the log keeps one entry per id.
+    fn new(job_id: &str, url: String, event: &str) -> Self {
+        Self {
+            id: crate::jobs::generate_id(),
+            job_id: job_id.to_string(),
+            url,
+            event: event.to_string(),
+            timestamp: crate::jobs::now_secs(),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            response_status: None,
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+/// Whether `url` can be used as a webhook target.
+pub fn is_valid_url(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https"))
+}
+
+/// `sha256=<hex>` HMAC of `body`, sent as `X-Dazzle-Signature`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    let digest = mac.finalize().into_bytes();
+    let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+    format!("sha256={hex}")
+}
+
+/// Deliver a finished job to every configured webhook plus `callback`, in
+/// the background.
+pub fn notify(state: &Arc<AppState>, job: &PrintJob, callback: Option<&str>) {
+    let event = match job.status {
+        JobStatus::Completed => "job.completed",
+        JobStatus::Failed => "job.failed",
+        _ => return,
+    };
+
+    let (mut urls, secret) = match state.config.read() {
+        Ok(config) => (
+            config.webhooks.urls.clone(),
+            config.webhooks.secret.clone().filter(|s| !s.is_empty()),
+        ),
+        Err(_) => return,
+    };
+    if let Some(callback) = callback {
+        if !urls.iter().any(|u| u == callback) {
+            urls.push(callback.to_string());
+        }
+    }
+    if urls.is_empty() {
+        return;
+    }
+
+    let body = match serde_json::to_vec(job) {
+        Ok(body) => body,
+        Err(e) => {
+            log::error!("Failed to serialize job {} for webhooks: {e}", job.id);
+            return;
+        }
+    };
+
+    for url in urls {
+        tokio::spawn(deliver(
+            state.clone(),
+            Delivery::new(&job.id, url, event),
+            body.clone(),
+            secret.clone(),
+        ));
+    }
+}
+
+async fn deliver(
+    state: Arc<AppState>,
+    mut delivery: Delivery,
+    body: Vec<u8>,
+    secret: Option<String>,
+) {
+    record(&state, &delivery);
+
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            delivery.status = DeliveryStatus::Failed;
+            delivery.error = Some(format!("Failed to create HTTP client: {e}"));
+            record(&state, &delivery);
+            return;
+        }
+    };
+
+    send(
+        &client,
+        &mut delivery,
+        &body,
+        secret.as_deref(),
+        FIRST_RETRY_DELAY,
+        |d| record(&state, d),
+    )
+    .await;
+
+    match delivery.status {
+        DeliveryStatus::Delivered => log::info!(
+            "Webhook {} for job {} delivered to {}",
+            delivery.event,
+            delivery.job_id,
+            delivery.url
+        ),
+        _ => log::warn!(
+            "Webhook {} for job {} to {} failed after {} attempts: {}",
+            delivery.event,
+            delivery.job_id,
+            delivery.url,
+            delivery.attempts,
+            delivery.error.as_deref().unwrap_or_default()
+        ),
+    }
+    state.app_handle.emit("webhook-delivery", &delivery).ok();
+}
+
+/// POST `body` until it's accepted or [`MAX_ATTEMPTS`] run out, calling
+/// `update` after every attempt.
+async fn send(
+    client: &reqwest::Client,
+    delivery: &mut Delivery,
+    body: &[u8],
+    secret: Option<&str>,
+    first_retry_delay: Duration,
+    mut update: impl FnMut(&Delivery),
+) {
+    let mut delay = first_retry_delay;
+
+    loop {
+        delivery.attempts += 1;
+
+        let mut request = client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Dazzle-Event", &delivery.event)
+            .header("X-Dazzle-Delivery", &delivery.id)
+            .body(body.to_vec());
+        if let Some(secret) = secret {
+            request = request.header("X-Dazzle-Signature", sign(secret, body));
+        }
+
+        match request.send().await {
+            Ok(response) => {
+                let status = response.status();
+                delivery.response_status = Some(status.as_u16());
+                if status.is_success() {
+                    delivery.status = DeliveryStatus::Delivered;
+                    delivery.error = None;
+                } else {
+                    delivery.error = Some(format!("Receiver answered {status}"));
+                }
+            }
+            Err(e) => {
+                delivery.response_status = None;
+                delivery.error = Some(e.to_string());
+            }
+        }
+
+        if delivery.status != DeliveryStatus::Delivered && delivery.attempts >= MAX_ATTEMPTS {
+            delivery.status = DeliveryStatus::Failed;
+        }
+        update(delivery);
+
+        if delivery.status != DeliveryStatus::Pending {
+            return;
+        }
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+}
+
+/// Insert or update `delivery` in the log, newest first.
+fn record(state: &AppState, delivery: &Delivery) {
+    if let Ok(mut log) = state.webhook_deliveries.write() {
+        match log.iter_mut().find(|d| d.id == delivery.id) {
+            Some(entry) => *entry = delivery.clone(),
+            None => {
+                log.insert(0, delivery.clone());
+                log.truncate(LOG_LEN);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use std::sync::Mutex;
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, Bytes)>>>;
+
+    /// A local receiver that answers 500 to the first `failures` requests.
+    async fn receiver(failures: usize) -> (String, Received) {
+        let received: Received = Arc::default();
+
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    move |State(received): State<Received>, headers: HeaderMap, body: Bytes| async move {
+                        let mut received = received.lock().unwrap();
+                        received.push((headers, body));
+                        if received.len() <= failures {
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        } else {
+                            StatusCode::NO_CONTENT
+                        }
+                    },
+                ),
+            )
+            .with_state(received.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (format!("http://{addr}/hook"), received)
+    }
+
+    fn delivery(url: &str) -> Delivery {
+        Delivery {
+            id: "d1".to_string(),
+            job_id: "j1".to_string(),
+            url: url.to_string(),
+            event: "job.completed".to_string(),
+            timestamp: 0,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            response_status: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn signature_is_hmac_sha256_hex() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn deliveries_to_each_url_get_their_own_id() {
+        let ids: std::collections::HashSet<String> = (0..100)
+            .map(|i| Delivery::new("j1", format!("http://hooks.local/{i}"), "job.completed").id)
+            .collect();
+        assert_eq!(ids.len(), 100);
+    }
+
+    #[test]
+    fn only_http_urls_are_valid() {
+        assert!(is_valid_url("https://backend.example.com/hooks/dazzle"));
+        assert!(is_valid_url("http://127.0.0.1:8080/hook"));
+        assert!(!is_valid_url("file:///etc/passwd"));
+        assert!(!is_valid_url("not a url"));
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_job_and_retries_failures() {
+        let (url, received) = receiver(2).await;
+        let mut delivery = delivery(&url);
+        let body = br#"{"id":"j1","status":"completed"}"#;
+        let mut updates = 0;
+
+        send(
+            &reqwest::Client::new(),
+            &mut delivery,
+            body,
+            Some("secret"),
+            Duration::from_millis(10),
+            |_| updates += 1,
+        )
+        .await;
+
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(delivery.response_status, Some(204));
+        assert_eq!(updates, 3);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        let (headers, received_body) = received.last().unwrap();
+        assert_eq!(received_body.as_ref(), body);
+        assert_eq!(headers["x-dazzle-event"], "job.completed");
+        assert_eq!(headers["x-dazzle-delivery"], "d1");
+        assert_eq!(headers["x-dazzle-signature"], sign("secret", body).as_str());
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let (url, received) = receiver(usize::MAX).await;
+        let mut delivery = delivery(&url);
+
+        send(
+            &reqwest::Client::new(),
+            &mut delivery,
+            b"{}",
+            None,
+            Duration::from_millis(1),
+            |_| {},
+        )
+        .await;
+
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+        assert_eq!(delivery.attempts, MAX_ATTEMPTS);
+        assert_eq!(delivery.response_status, Some(500));
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), MAX_ATTEMPTS as usize);
+        assert!(!received[0].0.contains_key("x-dazzle-signature"));
+    }
+}
@@ -1,5 +1,6 @@
-import { describe, it, expect } from 'vitest';
-import { render, screen } from '@testing-library/react';
+import { describe, it, expect, vi } from 'vitest';
+import { render, screen, waitFor } from '@testing-library/react';
+import userEvent from '@testing-library/user-event';
 import PrintLog from './PrintLog';
 import { useAppStore } from '@/store';
 
@@ -35,6 +36,68 @@ describe('PrintLog', () => {
     expect(screen.getByText('Brother QL-800')).toBeInTheDocument();
   });
 
+  it('shows the job reference', () => {
+    useAppStore.setState({
+      printJobs: [
+        {
+          id: '1',
+          printer: 'Zebra ZD420',
+          timestamp: 1700000000,
+          status: 'completed',
+          reference: 'SHIP-4471',
+        },
+      ],
+    });
+
+    render(<PrintLog />);
+    expect(screen.getByText('SHIP-4471')).toBeInTheDocument();
+  });
+
+  it('shows when a scheduled job prints', () => {
+    useAppStore.setState({
+      printJobs: [
+        {
+          id: '1',
+          printer: 'Zebra ZD420',
+          timestamp: 1700000000,
+          status: 'scheduled',
+          scheduled_for: 1700003600,
+        },
+      ],
+    });
+
+    render(<PrintLog />);
+    const time = new Date(1700003600 * 1000).toLocaleString();
+    expect(screen.getByTitle(`Prints at ${time}`)).toBeInTheDocument();
+  });
+
+  it('searches the job history', async () => {
+    const user = userEvent.setup();
+    const shipment = {
+      id: '2',
+      printer: 'Brother QL-800',
+      timestamp: 1700000060,
+      status: 'completed' as const,
+      reference: 'SHIP-4471',
+    };
+    const searchPrintJobs = vi.fn().mockResolvedValue([shipment]);
+
+    useAppStore.setState({
+      printJobs: [
+        { id: '1', printer: 'Zebra ZD420', timestamp: 1700000000, status: 'completed' },
+        shipment,
+      ],
+      searchPrintJobs,
+    });
+
+    render(<PrintLog />);
+    await user.type(screen.getByPlaceholderText('Search jobs'), 'SHIP');
+
+    expect(searchPrintJobs).toHaveBeenLastCalledWith('SHIP');
+    await waitFor(() => expect(screen.queryByText('Zebra ZD420')).not.toBeInTheDocument());
+    expect(screen.getByText('SHIP-4471')).toBeInTheDocument();
+  });
+
   it('shows section heading', () => {
     useAppStore.setState({ printJobs: [] });
     render(<PrintLog />);
@@ -1,29 +1,73 @@
-import { CheckCircle, XCircle, CircleNotch } from 'phosphor-react';
+import { CheckCircle, XCircle, CircleNotch, PauseCircle, Clock } from 'phosphor-react';
+import { useEffect, useState } from 'react';
 import { useAppStore } from '@/store';
+import type { PrintJob } from '@/types';
 
 export default function PrintLog() {
   const printJobs = useAppStore((s) => s.printJobs);
+  const searchPrintJobs = useAppStore((s) => s.searchPrintJobs);
+  const [search, setSearch] = useState('');
+  const [results, setResults] = useState<PrintJob[] | null>(null);
+
+  // Search on the backend, again whenever a job comes in or changes
+  useEffect(() => {
+    if (!search.trim()) {
+      setResults(null);
+      return;
+    }
+    let current = true;
+    searchPrintJobs(search)
+      .then((jobs) => current && setResults(jobs))
+      .catch(() => current && setResults([]));
+    return () => {
+      current = false;
+    };
+  }, [search, printJobs, searchPrintJobs]);
+
+  const jobs = results ?? printJobs;
 
   return (
     <div className="px-4 py-3 flex-1 min-h-0">
-      <h2 className="text-xs font-semibold tracking-widest text-app-muted uppercase mb-2">
-        Recent Jobs
-      </h2>
+      <div className="flex items-center gap-2 mb-2">
+        <h2 className="text-xs font-semibold tracking-widest text-app-muted uppercase">
+          Recent Jobs
+        </h2>
+        <input
+          type="search"
+          className="input-field ml-auto w-40 text-xs"
+          placeholder="Search jobs"
+          value={search}
+          onChange={(e) => setSearch(e.target.value)}
+        />
+      </div>
 
-      {printJobs.length === 0 ? (
+      {jobs.length === 0 ? (
         <p className="text-sm text-app-muted py-2">
-          No print jobs yet. Send ZPL to the server to get started.
+          {results
+            ? 'No jobs match your search.'
+            : 'No print jobs yet. Send ZPL to the server to get started.'}
         </p>
       ) : (
         <div className="space-y-0.5 overflow-y-auto">
-          {printJobs.map((job) => (
+          {jobs.map((job) => (
             <div
               key={job.id}
               className="flex items-center gap-2.5 px-3 py-2 rounded-md bg-app-gray text-sm"
-              title={job.error ?? job.zpl_preview ?? undefined}
+              title={
+                job.error ??
+                (job.scheduled_for
+                  ? `Prints at ${new Date(job.scheduled_for * 1000).toLocaleString()}`
+                  : job.zpl_preview) ??
+                undefined
+              }
             >
               <StatusIcon status={job.status} />
               <span className="truncate flex-1">{job.printer}</span>
+              {job.reference && (
+                <span className="text-[11px] text-app-muted truncate max-w-[40%]">
+                  {job.reference}
+                </span>
+              )}
               <span className="text-[11px] text-app-muted tabular-nums shrink-0">
                 {new Date(job.timestamp * 1000).toLocaleTimeString()}
               </span>
@@ -41,6 +85,10 @@ function StatusIcon({ status }: { status: string }) {
       return <CheckCircle size={16} weight="fill" className="text-app-green shrink-0" />;
     case 'failed':
       return <XCircle size={16} weight="fill" className="text-app-red shrink-0" />;
+    case 'scheduled':
+      return <Clock size={16} weight="fill" className="text-app-muted shrink-0" />;
+    case 'held':
+      return <PauseCircle size={16} weight="fill" className="text-app-yellow shrink-0" />;
     default:
       return <CircleNotch size={16} className="text-app-yellow shrink-0 animate-spin" />;
   }
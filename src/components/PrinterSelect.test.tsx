@@ -26,6 +26,19 @@ describe('PrinterSelect', () => {
     expect(screen.getByText('default')).toBeInTheDocument();
   });
 
+  it('marks paused printers', () => {
+    useAppStore.setState({
+      printers: [
+        { name: 'Zebra ZD420', is_default: true, paused: true },
+        { name: 'Brother QL-800', is_default: false },
+      ],
+      config: { port: 29100, selected_printer: 'Zebra ZD420' },
+    });
+
+    render(<PrinterSelect />);
+    expect(screen.getAllByText('paused')).toHaveLength(1);
+  });
+
   it('calls updateConfig when selecting a printer', async () => {
     const user = userEvent.setup();
     const updateConfig = vi.fn();
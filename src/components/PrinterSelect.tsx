@@ -36,8 +36,13 @@ export default function PrinterSelect() {
               >
                 <Printer size={16} weight={selected ? 'fill' : 'regular'} />
                 <span className="truncate">{p.name}</span>
+                {p.paused && (
+                  <span className="text-[10px] text-app-yellow ml-auto shrink-0">paused</span>
+                )}
                 {p.is_default && (
-                  <span className="text-[10px] text-app-muted ml-auto shrink-0">default</span>
+                  <span className={`text-[10px] text-app-muted shrink-0 ${p.paused ? '' : 'ml-auto'}`}>
+                    default
+                  </span>
                 )}
               </button>
             );
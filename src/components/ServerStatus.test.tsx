@@ -130,4 +130,47 @@ describe('ServerStatus', () => {
 
     expect(input).toHaveValue('8080');
   });
+
+  it('pauses and resumes printing', async () => {
+    const user = userEvent.setup();
+    const setPrintingPaused = vi.fn().mockResolvedValue(undefined);
+
+    useAppStore.setState({
+      serverRunning: true,
+      serverError: null,
+      config: { port: 29100, selected_printer: null },
+      printingPaused: false,
+      setPrintingPaused,
+    });
+
+    const { rerender } = render(<ServerStatus />);
+    await user.click(screen.getByText('Pause printing'));
+    expect(setPrintingPaused).toHaveBeenCalledWith(true);
+
+    useAppStore.setState({ printingPaused: true });
+    rerender(<ServerStatus />);
+    await user.click(screen.getByText('Resume printing'));
+    expect(setPrintingPaused).toHaveBeenCalledWith(false);
+  });
+
+  it('turns on HTTPS and exports the CA certificate', async () => {
+    const user = userEvent.setup();
+    const updateConfig = vi.fn().mockResolvedValue(undefined);
+    const exportTlsCa = vi.fn().mockResolvedValue('/home/me/Downloads/dazzle-local-ca.pem');
+
+    useAppStore.setState({
+      serverRunning: true,
+      serverError: null,
+      config: { port: 29100, selected_printer: null },
+      updateConfig,
+      exportTlsCa,
+    });
+
+    render(<ServerStatus />);
+    await user.click(screen.getByLabelText('Serve HTTPS on port 29443'));
+    expect(updateConfig).toHaveBeenCalledWith({ port: 29100, selected_printer: null, tls_enabled: true });
+
+    await user.click(screen.getByText('Export CA'));
+    expect(await screen.findByText('Saved to /home/me/Downloads/dazzle-local-ca.pem')).toBeInTheDocument();
+  });
 });
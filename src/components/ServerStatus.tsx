@@ -1,6 +1,6 @@
 import { useEffect, useState } from 'react';
-import { ArrowClockwise, Play, WarningCircle } from 'phosphor-react';
-import { useAppStore } from '@/store';
+import { ArrowClockwise, Pause, Play, WarningCircle } from 'phosphor-react';
+import { errorMessage, useAppStore } from '@/store';
 
 export default function ServerStatus() {
   const serverRunning = useAppStore((s) => s.serverRunning);
@@ -10,6 +10,9 @@ export default function ServerStatus() {
   const restartServer = useAppStore((s) => s.restartServer);
   const autostart = useAppStore((s) => s.autostart);
   const setAutostart = useAppStore((s) => s.setAutostart);
+  const printingPaused = useAppStore((s) => s.printingPaused);
+  const setPrintingPaused = useAppStore((s) => s.setPrintingPaused);
+  const exportTlsCa = useAppStore((s) => s.exportTlsCa);
   const [port, setPort] = useState(config.port.toString());
 
   // Sync port input when config changes externally
@@ -18,6 +21,7 @@ export default function ServerStatus() {
   }, [config.port]);
   const [saving, setSaving] = useState(false);
   const [restarting, setRestarting] = useState(false);
+  const [exported, setExported] = useState<string | null>(null);
 
   const applyPort = async () => {
     const newPort = parseInt(port, 10);
@@ -39,15 +43,33 @@ export default function ServerStatus() {
     }
   };
 
+  const handleExportCa = async () => {
+    try {
+      setExported(`Saved to ${await exportTlsCa()}`);
+    } catch (e) {
+      setExported(errorMessage(e));
+    }
+  };
+
   return (
     <div className="px-4 py-3 border-b">
       <div className="flex items-center gap-2">
         <div className={`w-2 h-2 rounded-full ${serverRunning ? 'bg-app-green' : 'bg-app-red'}`} />
         <span className="text-sm">{serverRunning ? 'Server running' : 'Server stopped'}</span>
+        <button
+          onClick={() => setPrintingPaused(!printingPaused)}
+          className={`ml-auto flex items-center gap-1.5 px-2.5 py-1 text-xs rounded transition-colors ${
+            printingPaused ? 'bg-red-950/40 text-red-400 hover:bg-red-950/60' : 'bg-app-dark hover:bg-app-lighter'
+          }`}
+          title={printingPaused ? 'Resume printing' : 'Refuse all new print jobs'}
+        >
+          {printingPaused ? <Play size={14} weight="fill" /> : <Pause size={14} weight="fill" />}
+          {printingPaused ? 'Resume printing' : 'Pause printing'}
+        </button>
         <button
           onClick={handleRestart}
           disabled={restarting}
-          className="ml-auto flex items-center gap-1.5 px-2.5 py-1 text-xs rounded bg-app-dark hover:bg-app-lighter transition-colors disabled:opacity-50"
+          className="flex items-center gap-1.5 px-2.5 py-1 text-xs rounded bg-app-dark hover:bg-app-lighter transition-colors disabled:opacity-50"
           title={serverRunning ? 'Restart server' : 'Start server'}
         >
           {serverRunning ? (
@@ -101,6 +123,26 @@ export default function ServerStatus() {
           Launch at login
         </label>
       </div>
+
+      <div className="flex items-center gap-3 mt-2">
+        <label className="flex items-center gap-1.5 text-xs text-app-muted cursor-pointer select-none">
+          <input
+            type="checkbox"
+            checked={config.tls_enabled ?? false}
+            onChange={(e) => updateConfig({ ...config, tls_enabled: e.target.checked })}
+            className="accent-app-accent"
+          />
+          Serve HTTPS on port {config.tls_port ?? 29443}
+        </label>
+        <button
+          onClick={handleExportCa}
+          className="ml-auto px-2.5 py-1 text-xs rounded bg-app-dark hover:bg-app-lighter transition-colors"
+          title="Save the local CA certificate to install as a trusted root"
+        >
+          Export CA
+        </button>
+      </div>
+      {exported && <p className="text-xs text-app-muted mt-1 break-all">{exported}</p>}
     </div>
   );
 }
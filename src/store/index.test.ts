@@ -17,6 +17,7 @@ function resetStore() {
     loading: true,
     initError: null,
     autostart: false,
+    printingPaused: false,
   });
 }
 
@@ -69,7 +70,8 @@ describe('useAppStore', () => {
         .mockResolvedValueOnce(config) // get_config
         .mockResolvedValueOnce(printJobs) // get_print_jobs
         .mockResolvedValueOnce(true) // get_server_running
-        .mockResolvedValueOnce(true); // get_autostart
+        .mockResolvedValueOnce(true) // get_autostart
+        .mockResolvedValueOnce([]); // get_scheduled_jobs
       mockListen.mockResolvedValue(() => {});
 
       await useAppStore.getState().init();
@@ -84,6 +86,29 @@ describe('useAppStore', () => {
       expect(state.autostart).toBe(true);
     });
 
+    it('lists scheduled jobs ahead of the history', async () => {
+      const scheduled: PrintJob = {
+        id: 'later',
+        printer: 'Zebra ZD420',
+        timestamp: 1700000100,
+        status: 'scheduled',
+        scheduled_for: 1700003600,
+      };
+      mockInvoke
+        .mockResolvedValueOnce(printers) // list_printers
+        .mockResolvedValueOnce(config) // get_config
+        .mockResolvedValueOnce(printJobs) // get_print_jobs
+        .mockResolvedValueOnce(true) // get_server_running
+        .mockResolvedValueOnce(true) // get_autostart
+        .mockResolvedValueOnce([scheduled]); // get_scheduled_jobs
+      mockListen.mockResolvedValue(() => {});
+
+      await useAppStore.getState().init();
+
+      expect(mockInvoke).toHaveBeenCalledWith('get_scheduled_jobs');
+      expect(useAppStore.getState().printJobs).toEqual([scheduled, ...printJobs]);
+    });
+
     it('auto-selects default printer when none configured', async () => {
       const noSelectionConfig: AppConfig = {
         port: 29100,
@@ -96,6 +121,7 @@ describe('useAppStore', () => {
         .mockResolvedValueOnce([]) // get_print_jobs
         .mockResolvedValueOnce(false) // get_server_running
         .mockResolvedValueOnce(false) // get_autostart
+        .mockResolvedValueOnce([]) // get_scheduled_jobs
         .mockResolvedValue(undefined); // set_config (fire-and-forget)
       mockListen.mockResolvedValue(() => {});
 
@@ -117,6 +143,7 @@ describe('useAppStore', () => {
         .mockResolvedValueOnce([])
         .mockResolvedValueOnce(false)
         .mockResolvedValueOnce(false) // get_autostart
+        .mockResolvedValueOnce([]) // get_scheduled_jobs
         .mockResolvedValue(undefined);
       mockListen.mockResolvedValue(() => {});
 
@@ -142,7 +169,8 @@ describe('useAppStore', () => {
         .mockResolvedValueOnce({ port: 29100, selected_printer: null })
         .mockResolvedValueOnce([])
         .mockResolvedValueOnce(false)
-        .mockResolvedValueOnce(false); // get_autostart
+        .mockResolvedValueOnce(false) // get_autostart
+        .mockResolvedValueOnce([]); // get_scheduled_jobs
       mockListen.mockResolvedValue(() => {});
 
       await useAppStore.getState().init();
@@ -151,6 +179,9 @@ describe('useAppStore', () => {
       expect(listenedEvents).toContain('print-job');
       expect(listenedEvents).toContain('server-status');
       expect(listenedEvents).toContain('server-error');
+      expect(listenedEvents).toContain('printing-paused');
+      expect(listenedEvents).toContain('printer-paused');
+      expect(listenedEvents).toContain('scheduled-job-cancelled');
     });
   });
 
@@ -202,6 +233,19 @@ describe('useAppStore', () => {
       expect(state.serverError).toContain('port in use');
       expect(state.serverRunning).toBe(false);
     });
+
+    it('shows the message of a structured error', async () => {
+      mockInvoke.mockRejectedValueOnce({
+        code: 'server_error',
+        message: 'Failed to bind 127.0.0.1:29100',
+        details: null,
+        job_id: null,
+      });
+
+      await useAppStore.getState().restartServer();
+
+      expect(useAppStore.getState().serverError).toBe('Failed to bind 127.0.0.1:29100');
+    });
   });
 
   describe('setAutostart', () => {
@@ -225,6 +269,17 @@ describe('useAppStore', () => {
     });
   });
 
+  describe('setPrintingPaused', () => {
+    it('pauses printing via invoke and updates state', async () => {
+      mockInvoke.mockResolvedValueOnce(undefined);
+
+      await useAppStore.getState().setPrintingPaused(true);
+
+      expect(mockInvoke).toHaveBeenCalledWith('set_printing_paused', { paused: true });
+      expect(useAppStore.getState().printingPaused).toBe(true);
+    });
+  });
+
   describe('print-job event handler', () => {
     it('adds new jobs to the front of the list', async () => {
       mockInvoke
@@ -232,7 +287,8 @@ describe('useAppStore', () => {
         .mockResolvedValueOnce({ port: 29100, selected_printer: null })
         .mockResolvedValueOnce([])
         .mockResolvedValueOnce(false)
-        .mockResolvedValueOnce(false); // get_autostart
+        .mockResolvedValueOnce(false) // get_autostart
+        .mockResolvedValueOnce([]); // get_scheduled_jobs
 
       // Capture the print-job event handler
       let printJobHandler: ((event: { payload: PrintJob }) => void) | undefined;
@@ -272,7 +328,8 @@ describe('useAppStore', () => {
         .mockResolvedValueOnce({ port: 29100, selected_printer: null })
         .mockResolvedValueOnce([existingJob])
         .mockResolvedValueOnce(false)
-        .mockResolvedValueOnce(false); // get_autostart
+        .mockResolvedValueOnce(false) // get_autostart
+        .mockResolvedValueOnce([]); // get_scheduled_jobs
 
       let printJobHandler: ((event: { payload: PrintJob }) => void) | undefined;
       mockListen.mockImplementation(async (event: string, handler: unknown) => {
@@ -296,6 +353,48 @@ describe('useAppStore', () => {
       expect(jobs[0].status).toBe('completed');
     });
 
+    it('replaces a scheduled fan-out row with its jobs once it prints', async () => {
+      const scheduled: PrintJob = {
+        id: 'sched1',
+        printer: 'Zebra, Brother',
+        timestamp: 1700000000,
+        status: 'scheduled',
+        scheduled_for: 1700003600,
+      };
+
+      mockInvoke
+        .mockResolvedValueOnce([])
+        .mockResolvedValueOnce({ port: 29100, selected_printer: null })
+        .mockResolvedValueOnce([])
+        .mockResolvedValueOnce(false)
+        .mockResolvedValueOnce(false) // get_autostart
+        .mockResolvedValueOnce([scheduled]); // get_scheduled_jobs
+
+      let printJobHandler: ((event: { payload: PrintJob }) => void) | undefined;
+      mockListen.mockImplementation(async (event: string, handler: unknown) => {
+        if (event === 'print-job') {
+          printJobHandler = handler as (event: { payload: PrintJob }) => void;
+        }
+        return () => {};
+      });
+
+      await useAppStore.getState().init();
+      expect(useAppStore.getState().printJobs).toEqual([scheduled]);
+
+      printJobHandler!({
+        payload: {
+          id: 'child1',
+          printer: 'Zebra',
+          timestamp: 1700003600,
+          status: 'printing',
+          parent_id: 'sched1',
+        },
+      });
+
+      const jobs = useAppStore.getState().printJobs;
+      expect(jobs.map((j) => j.id)).toEqual(['child1']);
+    });
+
     it('caps job list at 100 entries', async () => {
       // Pre-fill with 100 jobs
       const existingJobs: PrintJob[] = Array.from({ length: 100 }, (_, i) => ({
@@ -310,7 +409,8 @@ describe('useAppStore', () => {
         .mockResolvedValueOnce({ port: 29100, selected_printer: null })
         .mockResolvedValueOnce(existingJobs)
         .mockResolvedValueOnce(false)
-        .mockResolvedValueOnce(false); // get_autostart
+        .mockResolvedValueOnce(false) // get_autostart
+        .mockResolvedValueOnce([]); // get_scheduled_jobs
 
       let printJobHandler: ((event: { payload: PrintJob }) => void) | undefined;
       mockListen.mockImplementation(async (event: string, handler: unknown) => {
@@ -1,7 +1,7 @@
 import { create } from 'zustand';
 import { invoke } from '@tauri-apps/api/core';
 import { listen, type UnlistenFn } from '@tauri-apps/api/event';
-import type { Printer, PrintJob, AppConfig } from '@/types';
+import type { Printer, PrintJob, AppConfig, DazzleError, PrinterPaused } from '@/types';
 
 interface AppStore {
   printers: Printer[];
@@ -12,16 +12,29 @@ interface AppStore {
   loading: boolean;
   initError: string | null;
   autostart: boolean;
+  printingPaused: boolean;
 
   init: () => Promise<void>;
   refreshPrinters: () => Promise<void>;
   updateConfig: (config: AppConfig) => Promise<void>;
   restartServer: () => Promise<void>;
   setAutostart: (enabled: boolean) => Promise<void>;
+  setPrintingPaused: (paused: boolean) => Promise<void>;
+  exportTlsCa: () => Promise<string>;
+  searchPrintJobs: (search: string) => Promise<PrintJob[]>;
 }
 
 let listeners: UnlistenFn[] = [];
 
+/** The message of an error a command rejected with. */
+export function errorMessage(e: unknown): string {
+  const error = e as Partial<DazzleError> | null;
+  if (error && typeof error.message === 'string') {
+    return error.details ? `${error.message}: ${error.details}` : error.message;
+  }
+  return String(e);
+}
+
 export const useAppStore = create<AppStore>((set) => ({
   printers: [],
   config: { port: 29100, selected_printer: null },
@@ -31,6 +44,7 @@ export const useAppStore = create<AppStore>((set) => ({
   loading: true,
   initError: null,
   autostart: false,
+  printingPaused: false,
 
   init: async () => {
     // Clean up previous listeners (HMR safety)
@@ -38,13 +52,17 @@ export const useAppStore = create<AppStore>((set) => ({
     listeners = [];
 
     try {
-      const [printers, loadedConfig, printJobs, serverRunning, autostart] = await Promise.all([
-        invoke<Printer[]>('list_printers'),
-        invoke<AppConfig>('get_config'),
-        invoke<PrintJob[]>('get_print_jobs'),
-        invoke<boolean>('get_server_running'),
-        invoke<boolean>('get_autostart'),
-      ]);
+      const [printers, loadedConfig, history, serverRunning, autostart, scheduledJobs] =
+        await Promise.all([
+          invoke<Printer[]>('list_printers'),
+          invoke<AppConfig>('get_config'),
+          invoke<PrintJob[]>('get_print_jobs'),
+          invoke<boolean>('get_server_running'),
+          invoke<boolean>('get_autostart'),
+          invoke<PrintJob[]>('get_scheduled_jobs'),
+        ]);
+      // Jobs waiting on the schedule aren't in the history until they print
+      const printJobs = [...scheduledJobs, ...history];
 
       // Auto-select the system default printer if none is configured
       let config = loadedConfig;
@@ -56,13 +74,18 @@ export const useAppStore = create<AppStore>((set) => ({
 
       set({ printers, config, printJobs, serverRunning, autostart, loading: false });
     } catch (e) {
-      set({ loading: false, initError: String(e) });
+      set({ loading: false, initError: errorMessage(e) });
     }
 
     listeners.push(
       await listen<PrintJob>('print-job', (event) => {
         set((state) => {
-          const jobs = [...state.printJobs];
+          // A scheduled job sent to several printers prints as children of
+          // its row, so the row goes once the first of them starts
+          const parentId = event.payload.parent_id;
+          const jobs = state.printJobs.filter(
+            (j) => !(parentId && j.id === parentId && j.status === 'scheduled')
+          );
           const idx = jobs.findIndex((j) => j.id === event.payload.id);
           if (idx >= 0) {
             jobs[idx] = event.payload;
@@ -88,6 +111,31 @@ export const useAppStore = create<AppStore>((set) => ({
         set({ serverError: event.payload, serverRunning: false });
       })
     );
+
+    listeners.push(
+      await listen<boolean>('printing-paused', (event) => {
+        set({ printingPaused: event.payload });
+      })
+    );
+
+    listeners.push(
+      await listen<PrinterPaused>('printer-paused', (event) => {
+        const { printer, paused } = event.payload;
+        set((state) => ({
+          printers: state.printers.map((p) =>
+            (p.alias_for ?? p.name) === printer ? { ...p, paused } : p
+          ),
+        }));
+      })
+    );
+
+    listeners.push(
+      await listen<string>('scheduled-job-cancelled', (event) => {
+        set((state) => ({
+          printJobs: state.printJobs.filter((j) => j.id !== event.payload),
+        }));
+      })
+    );
   },
 
   refreshPrinters: async () => {
@@ -105,7 +153,7 @@ export const useAppStore = create<AppStore>((set) => ({
     try {
       await invoke('restart_server');
     } catch (e) {
-      set({ serverError: String(e), serverRunning: false });
+      set({ serverError: errorMessage(e), serverRunning: false });
     }
   },
 
@@ -113,4 +161,13 @@ export const useAppStore = create<AppStore>((set) => ({
     await invoke('set_autostart', { enabled });
     set({ autostart: enabled });
   },
+
+  setPrintingPaused: async (paused: boolean) => {
+    await invoke('set_printing_paused', { paused });
+    set({ printingPaused: paused });
+  },
+
+  exportTlsCa: () => invoke<string>('export_tls_ca'),
+
+  searchPrintJobs: (search: string) => invoke<PrintJob[]>('get_print_jobs', { search }),
 }));
@@ -1,18 +1,169 @@
 export interface Printer {
   name: string;
   is_default: boolean;
+  alias_for?: string;
+  paused?: boolean;
 }
 
 export interface PrintJob {
   id: string;
   printer: string;
   timestamp: number;
-  status: 'printing' | 'completed' | 'failed';
+  status: 'scheduled' | 'held' | 'printing' | 'completed' | 'failed';
   zpl_preview?: string;
   error?: string;
+  error_code?: ErrorCode;
+  dispatched_to?: string;
+  rule?: string;
+  parent_id?: string;
+  remote_job_id?: string;
+  idempotency_key?: string;
+  scheduled_for?: number;
+  reference?: string;
+  tags?: string[];
+  source?: string;
+  user?: string;
+  origin?: string;
+  user_agent?: string;
 }
 
 export interface AppConfig {
   port: number;
   selected_printer: string | null;
+  tls_enabled?: boolean;
+  tls_port?: number;
+  bind_address?: string;
+  lan?: LanConfig;
+  raw?: RawConfig;
+  lpd?: LpdConfig;
+  browser_print?: BrowserPrintConfig;
+  qz?: QzConfig;
+  aliases?: Record<string, string>;
+  pools?: Record<string, PoolConfig>;
+  rules?: RoutingRule[];
+  groups?: Record<string, string[]>;
+  webhooks?: WebhookConfig;
+  relay?: RelayConfig;
+  peer_tokens?: Record<string, string>;
+  idempotency_window_secs?: number;
+  dedupe?: DedupeConfig;
+  limits?: LimitsConfig;
+}
+
+export interface BrowserPrintConfig {
+  enabled: boolean;
+  port: number;
+  tls_port: number;
+}
+
+/** What commands reject with and the HTTP API answers errors with. */
+export interface DazzleError {
+  code: ErrorCode;
+  message: string;
+  details: string | null;
+  job_id: string | null;
+}
+
+export interface DedupeConfig {
+  enabled: boolean;
+  window_secs: number;
+}
+
+export type ErrorCode =
+  | 'empty_body'
+  | 'invalid_encoding'
+  | 'invalid_request'
+  | 'no_printer_selected'
+  | 'printer_not_found'
+  | 'job_not_found'
+  | 'printer_offline'
+  | 'spooler_error'
+  | 'peer_error'
+  | 'duplicate_job'
+  | 'rate_limited'
+  | 'too_many_jobs'
+  | 'printing_paused'
+  | 'unauthorized'
+  | 'forbidden'
+  | 'config_error'
+  | 'server_error'
+  | 'internal';
+
+export interface LanConfig {
+  enabled: boolean;
+  token: string | null;
+  allowed_networks: string[];
+  advertise_mdns: boolean;
+}
+
+export interface LimitsConfig {
+  per_origin_per_minute: number;
+  per_token_per_minute: number;
+  max_jobs: number;
+}
+
+export interface LpdConfig {
+  enabled: boolean;
+  port: number;
+  queues: Record<string, string>;
+}
+
+/** Sent with `printer-paused` events. */
+export interface PrinterPaused {
+  printer: string;
+  paused: boolean;
+}
+
+export interface PoolConfig {
+  members: string[];
+  strategy?: 'failover' | 'round_robin' | 'least_queued';
+}
+
+export interface QzConfig {
+  enabled: boolean;
+  port: number;
+  tls_port: number;
+  trusted_certificates: string[];
+}
+
+export interface RawConfig {
+  enabled: boolean;
+  port: number;
+  printer: string | null;
+}
+
+export interface RelayConfig {
+  enabled: boolean;
+  url: string;
+  station_key: string;
+  printer: string | null;
+}
+
+export interface RoutingRule {
+  name: string;
+  printer: string;
+  origin?: string | null;
+  headers?: Record<string, string>;
+  query?: Record<string, string>;
+  min_size?: number | null;
+  max_size?: number | null;
+  pattern?: string | null;
+}
+
+export interface WebhookConfig {
+  urls: string[];
+  secret: string | null;
+  allow_callbacks: boolean;
+}
+
+export interface WebhookDelivery {
+  id: string;
+  job_id: string;
+  url: string;
+  event: 'job.completed' | 'job.failed';
+  timestamp: number;
+  status: 'pending' | 'delivered' | 'failed';
+  attempts: number;
+  response_status: number | null;
+  error: string | null;
 }